@@ -1,91 +1,52 @@
-struct Computer {
-	opcodes: Vec<u64>,
-}
-
-impl Computer {
-	pub fn new(opcodes: Vec<u64>) -> Self {
-		Computer { opcodes }
-	}
-
-	pub fn run(&mut self) -> u64 {
-		for i in (0..self.opcodes.len()).step_by(4) {
-			if self.exec(i) {
-				break;
-			}
-		}
-
-		self.opcodes[0]
-	}
-
-	pub fn initialize(mut self, n: u64, m: u64) -> Self {
-		self.opcodes[1] = n;
-		self.opcodes[2] = m;
-
-		self
-	}
-
-	fn op<F>(&mut self, i: usize, f: F)
-	where
-		F: Fn(u64, u64) -> u64,
-	{
-		let opcodes = &mut self.opcodes;
-		let x = opcodes[opcodes[i + 1] as usize];
-		let y = opcodes[opcodes[i + 2] as usize];
-		let j = opcodes[i + 3] as usize;
-
-		opcodes[j] = f(x, y);
-	}
-
-	fn add(&mut self, i: usize) {
-		self.op(i, |x, y| x + y);
-	}
+mod symbolic;
 
-	fn multiply(&mut self, i: usize) {
-		self.op(i, |x, y| x * y);
-	}
+use intcode::{Buffer, Computer, Solver};
 
-	fn exec(&mut self, i: usize) -> bool {
-		match self.opcodes[i] {
-			1 => self.add(i),
-			2 => self.multiply(i),
-			99 => return true,
-			n @ _ => panic!("Unexpected opcode: {}", n),
-		}
+fn run(opcodes: &[i64], n: i64, m: i64) -> i64 {
+	let mut computer = Computer::new(opcodes.to_vec());
 
-		false
-	}
+	computer.set(1, n);
+	computer.set(2, m);
+	computer
+		.run(&mut Buffer::default())
+		.expect("Failed to run program");
+	computer.get(0)
 }
 
-fn find_gravity_assist(target: u64, opcodes: Vec<u64>) -> (u64, u64) {
-	for i in 0..100 {
-		for j in 0..100 {
-			let output = Computer::new(opcodes.clone()).initialize(i, j).run();
-
-			if output == target {
-				return (i, j);
-			}
-		}
+// Solved from the program's formula for address 0 when it has one, and by
+// searching every noun and verb otherwise
+fn find_gravity_assist(target: i64, opcodes: &[i64]) -> Option<(i64, i64)> {
+	if let Some(expr) = symbolic::formula(opcodes) {
+		return expr.solve(target, 0..100, 0..100);
 	}
 
-	panic!("Could not calculate a working gravity assist vector!");
+	// Day 2 programs run straight through, so anything still going after
+	// this many steps has been patched into a loop
+	let computer = Computer::new(opcodes.to_vec());
+	let solutions = Solver::new(&computer)
+		.patch(1, 0..100)
+		.patch(2, 0..100)
+		.limit(10_000)
+		.solve(|computer, _| computer.get(0) == target);
+
+	solutions.first().map(|values| (values[0], values[1]))
 }
 
 fn main() {
 	let target = 19690720;
 	let input = include_str!("../input.txt");
-	let opcodes = input
-		.trim()
-		.split(",")
-		.map(|n| n.parse::<u64>().expect("Failed to parse number"))
-		.collect::<Vec<_>>();
+	let opcodes = intcode::parse(input);
 
-	let output = Computer::new(opcodes.clone()).initialize(12, 2).run();
+	let output = run(&opcodes, 12, 2);
 
 	println!("Output: {}", output);
 
-	let (i, j) = find_gravity_assist(target, opcodes);
-
-	println!("Gravity Assist: {}", 100 * i + j);
+	match find_gravity_assist(target, &opcodes) {
+		Some((i, j)) => println!("Gravity Assist: {}", 100 * i + j),
+		None => {
+			println!("Could not calculate a working gravity assist vector!")
+		}
+	}
 }
 
 #[test]
@@ -94,7 +55,22 @@ fn test_run() {
 	let mut computer = Computer::new(opcodes);
 	let expected = vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50];
 
-	computer.run();
+	computer.run(&mut Buffer::default()).unwrap();
+
+	assert_eq!(computer.memory(), expected);
+}
+
+#[test]
+fn test_gravity_assist() {
+	let opcodes = intcode::parse(include_str!("../input.txt"));
+	let target = run(&opcodes, 57, 41);
+
+	assert_eq!(find_gravity_assist(target, &opcodes), Some((57, 41)));
+	assert_eq!(find_gravity_assist(-1, &opcodes), None);
+
+	// [0] = [noun] * [verb] depends on what they point at, so has no formula
+	let opcodes = vec![2, 0, 0, 0, 99];
 
-	assert_eq!(computer.opcodes, expected);
+	assert!(symbolic::formula(&opcodes).is_none());
+	assert_eq!(find_gravity_assist(2 * 2, &opcodes), Some((0, 0)));
 }
@@ -0,0 +1,254 @@
+use std::fmt;
+use std::ops::Range;
+
+use intcode::{Instruction, Mode, Opcode};
+
+// The coefficients of `noun * n + verb * v + constant`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Expr {
+	pub constant: i64,
+	pub noun: i64,
+	pub verb: i64,
+}
+
+impl Expr {
+	fn constant(n: i64) -> Self {
+		Expr {
+			constant: n,
+			..Expr::default()
+		}
+	}
+
+	fn as_constant(&self) -> Option<i64> {
+		match (self.noun, self.verb) {
+			(0, 0) => Some(self.constant),
+			_ => None,
+		}
+	}
+
+	// Arithmetic on expressions returns `None` where a coefficient
+	// overflows, as the program would fail there
+	fn add(self, other: Expr) -> Option<Expr> {
+		Some(Expr {
+			constant: self.constant.checked_add(other.constant)?,
+			noun: self.noun.checked_add(other.noun)?,
+			verb: self.verb.checked_add(other.verb)?,
+		})
+	}
+
+	fn scale(self, n: i64) -> Option<Expr> {
+		Some(Expr {
+			constant: self.constant.checked_mul(n)?,
+			noun: self.noun.checked_mul(n)?,
+			verb: self.verb.checked_mul(n)?,
+		})
+	}
+
+	pub fn eval(&self, noun: i64, verb: i64) -> Option<i64> {
+		self.constant
+			.checked_add(self.noun.checked_mul(noun)?)?
+			.checked_add(self.verb.checked_mul(verb)?)
+	}
+
+	// The first (noun, verb) in the same order a nested loop over the ranges
+	// would find it, without running anything. A noun whose arithmetic
+	// overflows is skipped.
+	pub fn solve(
+		&self,
+		target: i64,
+		nouns: Range<i64>,
+		verbs: Range<i64>,
+	) -> Option<(i64, i64)> {
+		for noun in nouns {
+			let rest = match self.eval(noun, 0) {
+				Some(n) => match target.checked_sub(n) {
+					Some(rest) => rest,
+					None => continue,
+				},
+				None => continue,
+			};
+
+			let verb = match self.verb {
+				0 if rest == 0 => verbs.start,
+				0 => continue,
+				n => match (rest.checked_rem(n), rest.checked_div(n)) {
+					(Some(0), Some(verb)) => verb,
+					_ => continue,
+				},
+			};
+
+			if verbs.contains(&verb) {
+				return Some((noun, verb));
+			}
+		}
+
+		None
+	}
+}
+
+impl fmt::Display for Expr {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} * noun + {} * verb + {}",
+			self.noun, self.verb, self.constant
+		)
+	}
+}
+
+// A cell read through an address that depends on the noun or verb could be
+// anything. That is fine as long as it never reaches the result.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value {
+	Known(Expr),
+	Unknown,
+}
+
+impl Value {
+	fn address(self) -> Option<usize> {
+		match self {
+			Value::Known(expr) => {
+				expr.as_constant().filter(|n| *n >= 0).map(|n| n as usize)
+			}
+			Value::Unknown => None,
+		}
+	}
+}
+
+// Runs the program with the noun and verb at addresses 1 and 2 left as
+// symbols, returning what ends up at address 0. Returns `None` if the result
+// is not linear in them, or the program does anything beyond add, multiply
+// and halt, or its control flow or addresses it writes depend on them, or
+// any of its arithmetic overflows.
+pub fn formula(opcodes: &[i64]) -> Option<Expr> {
+	let mut memory = opcodes
+		.iter()
+		.map(|n| Value::Known(Expr::constant(*n)))
+		.collect::<Vec<_>>();
+
+	if memory.len() < 3 {
+		return None;
+	}
+
+	memory[1] = Value::Known(Expr {
+		noun: 1,
+		..Expr::default()
+	});
+	memory[2] = Value::Known(Expr {
+		verb: 1,
+		..Expr::default()
+	});
+
+	let mut pos = 0;
+
+	loop {
+		let code = memory.get(pos)?.address()?;
+		let instruction = Instruction::decode(code as i64, pos).ok()?;
+
+		let arg = |i: usize| {
+			let value = *memory.get(pos + i + 1)?;
+
+			match instruction.modes[i] {
+				Mode::Immediate => Some(value),
+				Mode::Position => Some(match value.address() {
+					Some(addr) => *memory.get(addr)?,
+					None => Value::Unknown,
+				}),
+				Mode::Relative => None,
+			}
+		};
+
+		let value = match instruction.opcode {
+			Opcode::Add => match (arg(0)?, arg(1)?) {
+				(Value::Known(x), Value::Known(y)) => Value::Known(x.add(y)?),
+				_ => Value::Unknown,
+			},
+			// Only a product with a constant side stays linear
+			Opcode::Multiply => match (arg(0)?, arg(1)?) {
+				(Value::Known(x), Value::Known(y)) => {
+					match (x.as_constant(), y.as_constant()) {
+						(Some(n), _) => Value::Known(y.scale(n)?),
+						(_, Some(n)) => Value::Known(x.scale(n)?),
+						_ => Value::Unknown,
+					}
+				}
+				_ => Value::Unknown,
+			},
+			Opcode::Halt => {
+				return match memory[0] {
+					Value::Known(expr) => Some(expr),
+					Value::Unknown => None,
+				}
+			}
+			_ => return None,
+		};
+
+		if instruction.modes[2] != Mode::Position {
+			return None;
+		}
+
+		let target = memory.get(pos + 3)?.address()?;
+
+		*memory.get_mut(target)? = value;
+		pos += 4;
+	}
+}
+
+#[test]
+fn test_formula() {
+	let input = include_str!("../input.txt");
+	let opcodes = intcode::parse(input);
+	let expr = formula(&opcodes).unwrap();
+
+	for (noun, verb) in [(12, 2), (0, 0), (99, 99), (57, 41)] {
+		assert_eq!(
+			expr.eval(noun, verb),
+			Some(crate::run(&opcodes, noun, verb))
+		);
+	}
+
+	// [0] = 3 * noun + verb + 5
+	let opcodes = [
+		1, 0, 0, 3, 2, 1, 17, 18, 1, 18, 2, 18, 1, 18, 19, 0, 99, 3, 0, 5,
+	];
+	let expr = formula(&opcodes).unwrap();
+
+	assert_eq!(expr.to_string(), "3 * noun + 1 * verb + 5");
+}
+
+#[test]
+fn test_non_linear() {
+	// [0] = noun * verb
+	assert_eq!(formula(&[1102, 0, 0, 0, 99]), None);
+	// [0] = 2 * ([noun] + [verb]), which could be anything
+	assert_eq!(formula(&[1, 0, 0, 3, 1, 3, 3, 0, 99]), None);
+	// Input is not supported
+	assert_eq!(formula(&[3, 0, 0, 99]), None);
+	// Overflows, as it would on the VM
+	assert_eq!(formula(&[1101, 0, 0, 3, 1102, i64::MAX, 2, 0, 99]), None);
+	assert_eq!(formula(&[1101, 0, 0, 3, 1101, i64::MAX, 1, 0, 99]), None);
+}
+
+#[test]
+fn test_solve() {
+	let expr = Expr {
+		constant: 7,
+		noun: 100,
+		verb: 1,
+	};
+
+	assert_eq!(expr.solve(1234, 0..100, 0..100), Some((12, 27)));
+	assert_eq!(expr.solve(100_000, 0..100, 0..100), None);
+	assert_eq!(expr.solve(7, 0..100, 0..100), Some((0, 0)));
+	assert_eq!(Expr::constant(5).solve(5, 3..10, 4..10), Some((3, 4)));
+	assert_eq!(Expr::constant(5).solve(6, 0..10, 0..10), None);
+
+	let expr = Expr {
+		constant: 0,
+		noun: i64::MAX,
+		verb: -1,
+	};
+
+	assert_eq!(expr.solve(i64::MIN, 0..3, 0..100), None);
+	assert_eq!(expr.solve(-5, 0..3, 0..100), Some((0, 5)));
+}
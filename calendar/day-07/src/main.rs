@@ -1,272 +1,131 @@
-struct Computer {
-	pos: usize,
-	running: bool,
-	inputs: Vec<i64>,
-	outputs: Vec<i64>,
-	opcodes: Vec<i64>,
-}
-
-impl Computer {
-	pub fn parse(input: &str) -> Self {
-		let opcodes = input
-			.trim()
-			.split(',')
-			.map(|n| n.parse::<i64>())
-			.collect::<Result<Vec<_>, _>>()
-			.expect("Failed to parse number");
-
-		Computer::new(opcodes)
-	}
-
-	pub fn new(opcodes: Vec<i64>) -> Self {
-		Computer {
-			pos: 0,
-			running: true,
-			inputs: Vec::new(),
-			outputs: Vec::new(),
-			opcodes,
-		}
-	}
-
-	pub fn run(&mut self) -> Vec<i64> {
-		loop {
-			if self.exec() {
-				break;
-			}
-		}
+mod network;
+mod search;
 
-		std::mem::replace(&mut self.outputs, Vec::new())
-	}
-
-	fn index(&self, i: usize) -> usize {
-		let mode = self.opcodes[self.pos] / i64::pow(10, i as u32 + 2);
-		let j = self.pos + i + 1;
-
-		if mode % 10 == 0 {
-			self.opcodes[j] as usize
-		} else {
-			j
-		}
-	}
+use intcode::{Cached, Computer};
 
-	fn arg(&self, i: usize) -> i64 {
-		let j = self.index(i);
+use network::{Network, Run};
+use search::{search, Best};
 
-		self.opcodes[j]
-	}
-
-	fn op<F>(&mut self, f: F)
-	where
-		F: Fn(i64, i64) -> i64,
-	{
-		let x = self.arg(0);
-		let y = self.arg(1);
-		let j = self.index(2);
-
-		self.opcodes[j] = f(x, y);
-		self.pos += 4;
-	}
-
-	fn add(&mut self) {
-		self.op(|x, y| x + y);
-	}
-
-	fn multiply(&mut self) {
-		self.op(|x, y| x * y);
-	}
+struct Thrusters {
+	computer: Cached,
+}
 
-	fn input(&mut self) -> bool {
-		if self.inputs.is_empty() {
-			return true;
+impl Thrusters {
+	pub fn parse(input: &str) -> Self {
+		Thrusters {
+			computer: Cached::from(Computer::parse(input)),
 		}
-
-		let j = self.index(0);
-
-		self.opcodes[j] = self.inputs.remove(0);
-		self.pos += 2;
-
-		false
 	}
 
-	fn output(&mut self) {
-		let j = self.index(0);
+	// The thruster signal is the last signal out of the network
+	pub fn signal(&self, network: &Network) -> (i64, Run) {
+		let run = network
+			.run(&self.computer, &[0])
+			.unwrap_or_else(|err| panic!("{}", err));
 
-		self.outputs.push(self.opcodes[j]);
-		self.pos += 2;
+		(*run.outputs.last().expect("Expected an output"), run)
 	}
 
-	fn jump(&mut self, mode: bool) {
-		let x = self.arg(0);
-		let y = self.arg(1);
-
-		if (x != 0 && mode) || (x == 0 && !mode) {
-			self.pos = y as usize;
-		} else {
-			self.pos += 3;
-		}
-	}
-
-	fn compare(&mut self, mode: bool) {
-		let x = self.arg(0);
-		let y = self.arg(1);
-		let j = self.index(2);
-
-		self.opcodes[j] = if (mode && x < y) || (!mode && x == y) {
-			1
-		} else {
-			0
-		};
-
-		self.pos += 4;
+	pub fn find_max(&self) -> Best<Run> {
+		search(&[0, 1, 2, 3, 4], |phases| {
+			self.signal(&Network::chain(phases))
+		})
 	}
 
-	fn exec(&mut self) -> bool {
-		match self.opcodes[self.pos] % 100 {
-			1 => self.add(),
-			2 => self.multiply(),
-			3 => {
-				if self.input() {
-					return true;
-				}
-			}
-			4 => self.output(),
-			5 => self.jump(true),
-			6 => self.jump(false),
-			7 => self.compare(true),
-			8 => self.compare(false),
-			99 => {
-				self.running = false;
-
-				return true;
-			}
-			n => panic!("Unexpected opcode: {}", n),
-		}
-
-		false
+	pub fn max_feedback(&self) -> Best<Run> {
+		search(&[5, 6, 7, 8, 9], |phases| {
+			self.signal(&Network::ring(phases))
+		})
 	}
+}
 
-	pub fn find_max(&self) -> i64 {
-		let mut phases = [0, 1, 2, 3, 4];
-		let mut max = 0;
-
-		heap(&mut phases, |phases| {
-			let mut input = 0;
-
-			for phase in phases {
-				let mut computer = Computer::new(self.opcodes.clone());
-
-				computer.inputs = vec![*phase, input];
-
-				input = computer.run().pop().expect("Expected an output");
-			}
+fn usage() -> ! {
+	eprintln!("Usage: day-07 [--network <file> | --signals]");
+	std::process::exit(1);
+}
 
-			max = std::cmp::max(max, input);
+fn main() {
+	let input = include_str!("../input.txt");
+	let thrusters = Thrusters::parse(input);
+	let args = std::env::args().collect::<Vec<_>>();
+
+	if let Some(i) = args.iter().position(|arg| arg == "--network") {
+		let path = args.get(i + 1).unwrap_or_else(|| usage());
+		let rig =
+			std::fs::read_to_string(path).expect("Failed to read network");
+		let network = Network::parse(&rig).unwrap_or_else(|err| {
+			eprintln!("Invalid network: {}", err);
+			std::process::exit(1);
 		});
 
-		max
-	}
-
-	pub fn max_feedback(&self) -> i64 {
-		let mut phases = [5, 6, 7, 8, 9];
-		let mut max = 0;
-
-		heap(&mut phases, |phases| {
-			let mut amps = phases
-				.iter()
-				.map(|phase| {
-					let mut computer = Computer::new(self.opcodes.clone());
-
-					computer.inputs = vec![*phase];
-
-					computer
-				})
-				.collect::<Vec<_>>();
-
-			amps[0].inputs.push(0);
-
-			for i in 0.. {
-				let amp = &mut amps[i % phases.len()];
-
-				if !amp.running {
-					continue;
-				}
-
-				let mut outputs = amp.run();
+		let (signal, run) = thrusters.signal(&network);
 
-				if (i + 1) % phases.len() == 0 && !amp.running {
-					let output = outputs.pop().expect("Expected an output");
+		println!("Thruster: {}", signal);
+		println!("{}", run);
 
-					max = std::cmp::max(max, output);
-
-					break;
-				}
-
-				amps[(i + 1) % phases.len()].inputs.extend(outputs);
-			}
-		});
-
-		max
+		return;
 	}
-}
 
-fn heap<T, F>(a: &mut [T], mut output: F)
-where
-	F: FnMut(&[T]),
-{
-	generate(a.len(), a, &mut output);
-}
+	let signals = args.iter().any(|arg| arg == "--signals");
+	let best = thrusters.find_max();
+
+	println!("Max Thruster: {}", best.signal);
+	println!("Phases: {:?}", best.phases);
 
-fn generate<T, F>(k: usize, a: &mut [T], output: &mut F)
-where
-	F: FnMut(&[T]),
-{
-	if k == 1 {
-		return output(a);
+	if signals {
+		println!("Rounds: {}", best.detail.rounds());
+		println!("{}", best.detail);
 	}
 
-	generate(k - 1, a, output);
+	let best = thrusters.max_feedback();
 
-	for i in 0..(k - 1) {
-		if k % 2 == 0 {
-			a.swap(i, k - 1);
-		} else {
-			a.swap(0, k - 1);
-		}
+	println!("Max Thruster with Feedback: {}", best.signal);
+	println!("Phases: {:?}", best.phases);
 
-		generate(k - 1, a, output);
+	if signals {
+		println!("Rounds: {}", best.detail.rounds());
+		println!("{}", best.detail);
 	}
 }
 
-fn main() {
-	let input = include_str!("../input.txt");
-	let computer = Computer::parse(input);
-
-	println!("Max Thruster: {}", computer.find_max());
-	println!("Max Thruster with Feedback: {}", computer.max_feedback());
-}
-
 #[test]
 fn test_max_thruster() {
 	let input = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
-	let computer = Computer::parse(input);
+	let thrusters = Thrusters::parse(input);
 
-	assert_eq!(computer.find_max(), 43210);
+	let best = thrusters.find_max();
+
+	assert_eq!(best.phases, vec![4, 3, 2, 1, 0]);
+	assert_eq!(best.signal, 43210);
+	assert_eq!(best.detail.rounds(), 1);
 
 	let input = "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0";
-	let computer = Computer::parse(input);
+	let thrusters = Thrusters::parse(input);
+
+	let best = thrusters.find_max();
 
-	assert_eq!(computer.find_max(), 54321);
+	assert_eq!(best.phases, vec![0, 1, 2, 3, 4]);
+	assert_eq!(best.signal, 54321);
+	assert_eq!(best.detail.rounds(), 1);
 
 	let input = "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0";
-	let computer = Computer::parse(input);
+	let thrusters = Thrusters::parse(input);
 
-	assert_eq!(computer.find_max(), 65210);
+	let best = thrusters.find_max();
+
+	assert_eq!(best.phases, vec![1, 0, 4, 3, 2]);
+	assert_eq!(best.signal, 65210);
+	assert_eq!(best.detail.rounds(), 1);
 }
 
 #[test]
 fn test_feedback() {
 	let input = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
-	let computer = Computer::parse(input);
+	let thrusters = Thrusters::parse(input);
+
+	let best = thrusters.max_feedback();
 
-	assert_eq!(computer.max_feedback(), 139629729);
+	assert_eq!(best.phases, vec![9, 8, 7, 6, 5]);
+	assert_eq!(best.signal, 139629729);
+	assert_eq!(best.detail.rounds(), 5);
 }
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use intcode::{Cached, Io, VmError};
+
+#[cfg(test)]
+use intcode::Computer;
+
+// Signals passed to `run` enter the network through `in`, and whatever is
+// sent to `out` is returned from it.
+pub const INPUT: &str = "in";
+pub const OUTPUT: &str = "out";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+	pub line: usize,
+	pub message: String,
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}: {}", self.line, self.message)
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EdgeError {
+	pub from: String,
+	pub to: String,
+}
+
+impl fmt::Display for EdgeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Invalid edge: {} -> {}", self.from, self.to)
+	}
+}
+
+impl std::error::Error for EdgeError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeError {
+	pub node: String,
+	pub error: VmError,
+}
+
+impl fmt::Display for NodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.node, self.error)
+	}
+}
+
+impl std::error::Error for NodeError {}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Node {
+	name: String,
+	phases: Vec<i64>,
+}
+
+// A directed graph of machines all running the same program. Each machine
+// first reads its phases, then whatever the nodes with edges into it send,
+// and sends each of its outputs along every edge out of it. Signals from
+// several nodes into one arrive in whatever order the nodes produce them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Network {
+	nodes: Vec<Node>,
+	edges: Vec<(String, String)>,
+}
+
+// What came out of a network, and every signal each node sent in order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Run {
+	pub outputs: Vec<i64>,
+	pub sent: Vec<(String, Vec<i64>)>,
+}
+
+impl Run {
+	// In a ring every amplifier sends one signal per trip around it
+	pub fn rounds(&self) -> usize {
+		self.sent
+			.iter()
+			.map(|(_, sent)| sent.len())
+			.max()
+			.unwrap_or(0)
+	}
+
+	pub fn round(&self, i: usize) -> Vec<Option<i64>> {
+		self.sent
+			.iter()
+			.map(|(_, sent)| sent.get(i).cloned())
+			.collect()
+	}
+}
+
+impl fmt::Display for Run {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:>5}", "round")?;
+
+		for (name, _) in self.sent.iter() {
+			write!(f, " {:>10}", name)?;
+		}
+
+		for i in 0..self.rounds() {
+			write!(f, "\n{:>5}", i + 1)?;
+
+			for signal in self.round(i) {
+				match signal {
+					Some(signal) => write!(f, " {:>10}", signal)?,
+					None => write!(f, " {:>10}", "-")?,
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+// Reads and writes one node's channels, keeping what it sent
+struct Link {
+	input: Receiver<i64>,
+	outputs: Vec<Sender<i64>>,
+	sent: Vec<i64>,
+}
+
+impl Io for Link {
+	fn input(&mut self) -> Option<i64> {
+		self.input.recv().ok()
+	}
+
+	fn output(&mut self, n: i64) {
+		self.sent.push(n);
+
+		for output in self.outputs.iter() {
+			let _ = output.send(n);
+		}
+	}
+}
+
+impl Network {
+	pub fn new() -> Self {
+		Network::default()
+	}
+
+	// Amplifiers `a`, `b`, ... in a line from `in` to `out`
+	pub fn chain(phases: &[i64]) -> Self {
+		let mut network = Network::new();
+		let mut prev = INPUT.to_string();
+
+		for (name, phase) in names(phases.len()).into_iter().zip(phases) {
+			network.node(&name, &[*phase]).connect(&prev, &name);
+			prev = name;
+		}
+
+		network.connect(&prev, OUTPUT);
+		network
+	}
+
+	// A chain whose last amplifier also feeds back into the first
+	pub fn ring(phases: &[i64]) -> Self {
+		let mut network = Network::chain(phases);
+		let names = names(phases.len());
+
+		if let (Some(first), Some(last)) = (names.first(), names.last()) {
+			network.connect(last, first);
+		}
+
+		network
+	}
+
+	pub fn node(&mut self, name: &str, phases: &[i64]) -> &mut Self {
+		match self.nodes.iter_mut().find(|node| node.name == name) {
+			Some(node) => node.phases = phases.to_vec(),
+			None => self.nodes.push(Node {
+				name: name.to_string(),
+				phases: phases.to_vec(),
+			}),
+		}
+
+		self
+	}
+
+	// Nothing can be sent to `in` or from `out`
+	pub fn edge(
+		&mut self,
+		from: &str,
+		to: &str,
+	) -> Result<&mut Self, EdgeError> {
+		if from == OUTPUT || to == INPUT || !is_name(from) || !is_name(to) {
+			return Err(EdgeError {
+				from: from.to_string(),
+				to: to.to_string(),
+			});
+		}
+
+		Ok(self.connect(from, to))
+	}
+
+	fn connect(&mut self, from: &str, to: &str) -> &mut Self {
+		for name in [from, to] {
+			if name != INPUT
+				&& name != OUTPUT
+				&& !self.nodes.iter().any(|node| node.name == name)
+			{
+				self.node(name, &[]);
+			}
+		}
+
+		self.edges.push((from.to_string(), to.to_string()));
+		self
+	}
+
+	// One statement per line, `#` starts a comment:
+	//
+	//     a = 5            node `a` reads 5 before anything else
+	//     b = 6, 1         phases can be several values, or none
+	//     in -> a -> b     edges, nodes are created on first use
+	//     b -> a, out      fan out to several nodes at once
+	pub fn parse(input: &str) -> Result<Self, ParseError> {
+		let mut network = Network::new();
+
+		for (i, line) in input.lines().enumerate() {
+			let line = line.split('#').next().unwrap_or("").trim();
+			let error = |message: String| ParseError {
+				line: i + 1,
+				message,
+			};
+
+			if line.is_empty() {
+				continue;
+			}
+
+			if let Some((name, phases)) = line.split_once('=') {
+				let name = name.trim();
+
+				if !is_name(name) || name == INPUT || name == OUTPUT {
+					return Err(error(format!("Invalid node: {}", name)));
+				}
+
+				let phases = phases
+					.split(',')
+					.map(str::trim)
+					.filter(|n| !n.is_empty())
+					.map(|n| n.parse::<i64>())
+					.collect::<Result<Vec<_>, _>>()
+					.map_err(|_| error(format!("Invalid phases: {}", line)))?;
+
+				network.node(name, &phases);
+				continue;
+			}
+
+			let groups = line
+				.split("->")
+				.map(|group| {
+					group.split(',').map(str::trim).collect::<Vec<_>>()
+				})
+				.collect::<Vec<_>>();
+
+			if groups.len() < 2 {
+				return Err(error(format!("Expected an edge: {}", line)));
+			}
+
+			for name in groups.iter().flatten() {
+				if !is_name(name) {
+					return Err(error(format!("Invalid node: {}", name)));
+				}
+			}
+
+			for pair in groups.windows(2) {
+				for from in pair[0].iter() {
+					for to in pair[1].iter() {
+						network
+							.edge(from, to)
+							.map_err(|err| error(err.to_string()))?;
+					}
+				}
+			}
+		}
+
+		Ok(network)
+	}
+
+	// Runs every node on its own thread until they have all stopped. A node
+	// that stops for any reason other than halting, including running out of
+	// input because everything feeding it has stopped, is an error. A cycle
+	// that never receives a signal waits forever.
+	pub fn run(
+		&self,
+		computer: &Cached,
+		inputs: &[i64],
+	) -> Result<Run, NodeError> {
+		let (output, outputs) = channel();
+		let mut senders = HashMap::new();
+		let mut receivers = Vec::new();
+
+		for node in self.nodes.iter() {
+			let (sender, receiver) = channel();
+
+			for phase in node.phases.iter() {
+				let _ = sender.send(*phase);
+			}
+
+			senders.insert(node.name.as_str(), sender);
+			receivers.push(receiver);
+		}
+
+		let targets = |from: &str| {
+			self.edges
+				.iter()
+				.filter(|(source, _)| source == from)
+				.map(|(_, to)| match to.as_str() {
+					OUTPUT => output.clone(),
+					to => senders[to].clone(),
+				})
+				.collect::<Vec<_>>()
+		};
+
+		for sender in targets(INPUT) {
+			for n in inputs {
+				let _ = sender.send(*n);
+			}
+		}
+
+		let links = self
+			.nodes
+			.iter()
+			.zip(receivers)
+			.map(|(node, input)| Link {
+				input,
+				outputs: targets(&node.name),
+				sent: Vec::new(),
+			})
+			.collect::<Vec<_>>();
+
+		// Only the nodes hold senders now, so a node's input closes once
+		// everything feeding it has stopped
+		drop(senders);
+		drop(output);
+
+		thread::scope(|scope| {
+			let handles = links
+				.into_iter()
+				.map(|mut link| {
+					let mut computer = computer.clone();
+
+					scope.spawn(move || (computer.run(&mut link), link.sent))
+				})
+				.collect::<Vec<_>>();
+
+			let mut run = Run {
+				outputs: outputs.iter().collect(),
+				sent: Vec::new(),
+			};
+
+			for (node, handle) in self.nodes.iter().zip(handles) {
+				let (result, sent) = handle.join().expect("Node panicked");
+
+				if let Err(error) = result {
+					return Err(NodeError {
+						node: node.name.clone(),
+						error,
+					});
+				}
+
+				run.sent.push((node.name.clone(), sent));
+			}
+
+			Ok(run)
+		})
+	}
+}
+
+fn names(n: usize) -> Vec<String> {
+	(0..n)
+		.map(|i| match i < 26 {
+			true => ((b'a' + i as u8) as char).to_string(),
+			false => format!("amp{}", i),
+		})
+		.collect()
+}
+
+fn is_name(s: &str) -> bool {
+	!s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[test]
+fn test_parse() {
+	let network = Network::parse(
+		"# two amplifiers in a ring
+		a = 5
+		b = 6, 1
+		in -> a -> b -> out
+		b -> a",
+	)
+	.unwrap();
+
+	let mut expected = Network::new();
+
+	expected.node("a", &[5]).node("b", &[6, 1]);
+
+	for (from, to) in [(INPUT, "a"), ("a", "b"), ("b", OUTPUT), ("b", "a")] {
+		expected.edge(from, to).unwrap();
+	}
+
+	assert_eq!(network, expected);
+	assert!(expected.edge("a", INPUT).is_err());
+	assert!(expected.edge(OUTPUT, "a").is_err());
+	assert!(expected.edge("a", "").is_err());
+	assert_eq!(network, expected);
+	assert_eq!(Network::ring(&[5, 6]).edges.len(), 4);
+
+	assert!(Network::parse("a -> ").is_err());
+	assert!(Network::parse("a").is_err());
+	assert!(Network::parse("in = 5").is_err());
+	assert!(Network::parse("a -> in").is_err());
+	assert!(Network::parse("a = x").is_err());
+}
+
+#[test]
+fn test_fan_out_in() {
+	// Reads two numbers and outputs their sum
+	let computer =
+		Cached::from(Computer::parse("3,11,3,12,1,11,12,12,4,12,99,0,0"));
+
+	let network = Network::parse(
+		"a = 1
+		b = 10
+		c = 100
+		in -> a -> b, c -> out",
+	)
+	.unwrap();
+
+	let mut outputs = network.run(&computer, &[0]).unwrap().outputs;
+
+	outputs.sort_unstable();
+
+	assert_eq!(outputs, vec![11, 101]);
+
+	let network = Network::parse(
+		"a = 1
+		b = 10
+		c = 100
+		in -> a -> b, c -> d -> out",
+	)
+	.unwrap();
+
+	assert_eq!(network.run(&computer, &[0]).unwrap().outputs, vec![112]);
+	assert_eq!(
+		network.run(&computer, &[]),
+		Err(NodeError {
+			node: "a".to_string(),
+			error: VmError::InputStarved { addr: 2 }
+		})
+	);
+}
+
+#[test]
+fn test_ring() {
+	let input = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,\
+	             -1,28,1005,28,6,99,0,0,5";
+	let computer = Cached::from(Computer::parse(input));
+	let network = Network::ring(&[9, 8, 7, 6, 5]);
+
+	let run = network.run(&computer, &[0]).unwrap();
+
+	assert_eq!(run.outputs.last(), Some(&139629729));
+	assert_eq!(run.rounds(), 5);
+	assert_eq!(run.to_string().lines().count(), 6);
+}
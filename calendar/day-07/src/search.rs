@@ -0,0 +1,122 @@
+use std::thread;
+
+// The winning phases with whatever the search recorded about them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Best<T> {
+	pub phases: Vec<i64>,
+	pub signal: i64,
+	pub detail: T,
+}
+
+fn factorial(n: usize) -> usize {
+	(1..=n)
+		.try_fold(1usize, |acc, i| acc.checked_mul(i))
+		.expect("Too many phases to search")
+}
+
+// The `k`th permutation in lexicographic order of positions, read off the
+// factorial number system
+pub fn permutation(phases: &[i64], mut k: usize) -> Vec<i64> {
+	let mut remaining = phases.to_vec();
+	let mut permutation = Vec::with_capacity(phases.len());
+
+	for i in (0..phases.len()).rev() {
+		let f = factorial(i);
+
+		permutation.push(remaining.remove(k / f));
+		k %= f;
+	}
+
+	permutation
+}
+
+// Tries every ordering of `phases`, splitting them evenly between a thread per
+// core. Ties go to the ordering that comes first.
+pub fn search<T, F>(phases: &[i64], evaluate: F) -> Best<T>
+where
+	T: Send,
+	F: Fn(&[i64]) -> (i64, T) + Sync,
+{
+	let total = factorial(phases.len());
+	let threads = thread::available_parallelism()
+		.map(|n| n.get())
+		.unwrap_or(1)
+		.min(total);
+	let chunk = total.div_ceil(threads);
+	let evaluate = &evaluate;
+
+	thread::scope(|scope| {
+		let handles = (0..threads)
+			.map(|i| {
+				scope.spawn(move || {
+					let mut best: Option<Best<T>> = None;
+
+					for k in (i * chunk)..total.min((i + 1) * chunk) {
+						let phases = permutation(phases, k);
+						let (signal, detail) = evaluate(&phases);
+
+						if best.as_ref().is_none_or(|best| signal > best.signal)
+						{
+							best = Some(Best {
+								phases,
+								signal,
+								detail,
+							});
+						}
+					}
+
+					best
+				})
+			})
+			.collect::<Vec<_>>();
+
+		handles
+			.into_iter()
+			.filter_map(|handle| handle.join().expect("Search panicked"))
+			.fold(None, |best: Option<Best<T>>, next| match best {
+				Some(best) if best.signal >= next.signal => Some(best),
+				_ => Some(next),
+			})
+			.expect("Expected a permutation")
+	})
+}
+
+#[test]
+fn test_permutation() {
+	let permutations = (0..6)
+		.map(|k| permutation(&[1, 2, 3], k))
+		.collect::<Vec<_>>();
+
+	assert_eq!(
+		permutations,
+		vec![
+			vec![1, 2, 3],
+			vec![1, 3, 2],
+			vec![2, 1, 3],
+			vec![2, 3, 1],
+			vec![3, 1, 2],
+			vec![3, 2, 1],
+		]
+	);
+}
+
+#[test]
+fn test_search() {
+	// Weights each phase by its position, best with the largest phase last
+	let weigh = |phases: &[i64]| {
+		let weight = phases
+			.iter()
+			.enumerate()
+			.map(|(i, phase)| i as i64 * phase)
+			.sum();
+
+		(weight, phases.len())
+	};
+
+	let best = search(&[3, 1, 4, 1, 5, 9, 2], weigh);
+
+	assert_eq!(best.phases, vec![1, 1, 2, 3, 4, 5, 9]);
+	assert_eq!(best.signal, 109);
+	assert_eq!(best.detail, 7);
+	assert_eq!(search(&[], weigh).signal, 0);
+}
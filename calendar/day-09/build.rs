@@ -0,0 +1,16 @@
+use std::path::Path;
+
+// Compiles the BOOST program to Rust
+fn main() {
+	let input =
+		std::fs::read_to_string("input.txt").expect("Failed to read program");
+	let out = std::env::var("OUT_DIR").expect("Missing OUT_DIR");
+
+	std::fs::write(
+		Path::new(&out).join("boost.rs"),
+		intcode::transpile(&intcode::parse(&input)),
+	)
+	.expect("Failed to write program");
+
+	println!("cargo:rerun-if-changed=input.txt");
+}
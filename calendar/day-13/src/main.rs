@@ -1,85 +1,74 @@
 use std::collections::HashMap;
 
-struct Computer {
-	base: i64,
-	pos: usize,
-	output: Vec<i64>,
-	game: Game,
-	opcodes: HashMap<usize, i64>,
-}
+use intcode::{
+	Access, Action, Cached, Computer, Debugger, Device, Persist, Profile,
+	SnapshotError, Watcher,
+};
 
-impl Computer {
-	pub fn new(nums: Vec<i64>) -> Self {
-		let mut opcodes = HashMap::new();
+// Where this input keeps the score. The program sends it with `OUT [386]`
+// right after `OUT #-1` and `OUT #0`, at 73 and again at 441 where it adds
+// the points for a broken block. See `test_score_cell`.
+const SCORE: usize = 386;
 
-		for (i, n) in nums.into_iter().enumerate() {
-			opcodes.insert(i, n);
-		}
+#[derive(Clone, Copy, PartialEq)]
+enum Tile {
+	Empty,
+	Wall,
+	Block,
+	Paddle,
+	Ball,
+}
 
-		Computer {
-			base: 0,
-			pos: 0,
-			output: Vec::new(),
-			game: Game::new(),
-			opcodes,
+impl From<i64> for Tile {
+	fn from(n: i64) -> Self {
+		match n {
+			0 => Tile::Empty,
+			1 => Tile::Wall,
+			2 => Tile::Block,
+			3 => Tile::Paddle,
+			4 => Tile::Ball,
+			n => panic!("Unexpected tile id: {}", n),
 		}
 	}
+}
 
-	pub fn run(&mut self) {
-		loop {
-			if self.exec() {
-				break;
-			}
+impl From<Tile> for i64 {
+	fn from(tile: Tile) -> Self {
+		match tile {
+			Tile::Empty => 0,
+			Tile::Wall => 1,
+			Tile::Block => 2,
+			Tile::Paddle => 3,
+			Tile::Ball => 4,
 		}
 	}
+}
 
-	fn get(&self, i: usize) -> i64 {
-		self.opcodes.get(&i).cloned().unwrap_or(0)
-	}
-
-	fn index(&self, i: usize) -> usize {
-		let mode = self.get(self.pos) / i64::pow(10, i as u32 + 2);
-		let j = self.pos + i + 1;
+struct Game {
+	output: Vec<i64>,
+	tiles: HashMap<(i64, i64), Tile>,
+	score: i64,
+}
 
-		match mode % 10 {
-			0 => self.get(j) as usize,
-			2 => (self.get(j) + self.base) as usize,
-			_ => j,
+impl Game {
+	pub fn new() -> Self {
+		Game {
+			output: Vec::new(),
+			tiles: HashMap::new(),
+			score: 0,
 		}
 	}
 
-	fn arg(&self, i: usize) -> i64 {
-		let j = self.index(i);
-
-		self.get(j)
-	}
-
-	fn op<F>(&mut self, f: F)
-	where
-		F: Fn(i64, i64) -> i64,
-	{
-		let x = self.arg(0);
-		let y = self.arg(1);
-		let j = self.index(2);
-
-		self.opcodes.insert(j, f(x, y));
-		self.pos += 4;
-	}
-
-	fn add(&mut self) {
-		self.op(|x, y| x + y);
-	}
-
-	fn multiply(&mut self) {
-		self.op(|x, y| x * y);
+	pub fn count(&self, id: Tile) -> usize {
+		self.tiles.values().filter(|t| t == &&id).count()
 	}
+}
 
-	fn input(&mut self) {
-		let j = self.index(0);
-
-		let paddle = self.game.tiles.iter().find(|(_, t)| t == &&Tile::Paddle);
+impl Device for Game {
+	fn send(&mut self) -> Option<i64> {
+		let paddle = self.tiles.iter().find(|(_, t)| t == &&Tile::Paddle);
 
-		let ball = self.game.tiles.iter().find(|(_, t)| t == &&Tile::Ball);
+		let ball = self.tiles.iter().find(|(_, t)| t == &&Tile::Ball);
 
 		let input = if let (Some(paddle), Some(ball)) = (paddle, ball) {
 			match (paddle.0).0.cmp(&(ball.0).0) {
@@ -91,142 +80,243 @@ impl Computer {
 			0
 		};
 
-		self.opcodes.insert(j, input);
-		self.pos += 2;
+		Some(input)
 	}
 
-	fn output(&mut self) {
-		let j = self.index(0);
-
-		self.output.push(self.get(j));
-		self.pos += 2;
+	fn receive(&mut self, n: i64) {
+		self.output.push(n);
 
 		if self.output.len() >= 3 {
 			let x = self.output[0];
 			let y = self.output[1];
 
 			if x == -1 && y == 0 {
-				self.game.score = self.output[2];
+				self.score = self.output[2];
 			} else {
-				self.game.tiles.insert((x, y), Tile::from(self.output[2]));
+				self.tiles.insert((x, y), Tile::from(self.output[2]));
 			}
 
 			self.output.clear();
 		}
 	}
+}
 
-	fn increment_base(&mut self) {
-		let n = self.arg(0);
+impl Persist for Game {
+	fn save(&self) -> String {
+		let mut lines = vec![
+			format!("score {}", self.score),
+			format!("output {}", intcode::join_list(&self.output)),
+		];
 
-		self.base += n;
-		self.pos += 2;
-	}
+		let mut tiles = self.tiles.iter().collect::<Vec<_>>();
 
-	fn jump(&mut self, mode: bool) {
-		let x = self.arg(0);
-		let y = self.arg(1);
+		tiles.sort_by_key(|((x, y), _)| (*y, *x));
 
-		if (x != 0 && mode) || (x == 0 && !mode) {
-			self.pos = y as usize;
-		} else {
-			self.pos += 3;
+		for ((x, y), tile) in tiles {
+			lines.push(format!("tile {},{},{}", x, y, i64::from(*tile)));
 		}
-	}
-
-	fn compare(&mut self, mode: bool) {
-		let x = self.arg(0);
-		let y = self.arg(1);
-		let j = self.index(2);
-
-		let value = if (mode && x < y) || (!mode && x == y) {
-			1
-		} else {
-			0
-		};
 
-		self.opcodes.insert(j, value);
-		self.pos += 4;
+		lines.join("\n")
 	}
 
-	fn exec(&mut self) -> bool {
-		match self.get(self.pos) % 100 {
-			1 => self.add(),
-			2 => self.multiply(),
-			3 => self.input(),
-			4 => self.output(),
-			5 => self.jump(true),
-			6 => self.jump(false),
-			7 => self.compare(true),
-			8 => self.compare(false),
-			9 => self.increment_base(),
-			99 => return true,
-			n => panic!("Unexpected opcode: {}", n),
+	fn restore(state: &str) -> Result<Self, SnapshotError> {
+		let mut game = Game::new();
+
+		for line in state.lines().map(str::trim).filter(|l| !l.is_empty()) {
+			let mut parts = line.splitn(2, ' ');
+			let key = parts.next().unwrap_or("");
+			let values = intcode::parse_list(parts.next().unwrap_or(""))?;
+
+			match (key, values.as_slice()) {
+				("score", [score]) => game.score = *score,
+				("output", output) => game.output = output.to_vec(),
+				("tile", [x, y, id]) if (0..=4).contains(id) => {
+					game.tiles.insert((*x, *y), Tile::from(*id));
+				}
+				_ => {
+					return Err(SnapshotError(format!(
+						"Invalid line: {}",
+						line
+					)))
+				}
+			}
 		}
 
-		false
+		Ok(game)
 	}
 }
 
-#[derive(PartialEq)]
-enum Tile {
-	Empty,
-	Wall,
-	Block,
-	Paddle,
-	Ball,
+fn usage() -> ! {
+	eprintln!(
+		"Usage: day-13 [--debug | --snapshot <steps> <file> | \
+		 --resume <file> | --profile [file] | --scores]"
+	);
+	std::process::exit(1);
 }
 
-impl From<i64> for Tile {
-	fn from(n: i64) -> Self {
-		match n {
-			0 => Tile::Empty,
-			1 => Tile::Wall,
-			2 => Tile::Block,
-			3 => Tile::Paddle,
-			4 => Tile::Ball,
-			n => panic!("Unexpected tile id: {}", n),
+fn main() {
+	let input = include_str!("../input.txt");
+	let computer = Computer::parse(input);
+
+	if std::env::args().any(|arg| arg == "--debug") {
+		let mut debugger = Debugger::new(computer);
+		let stdin = std::io::stdin();
+
+		debugger.computer.set(0, 2);
+		debugger
+			.repl(&mut Game::new(), stdin.lock(), std::io::stdout())
+			.expect("Failed to run debugger");
+
+		return;
+	}
+
+	let args = std::env::args().collect::<Vec<_>>();
+	let flag = |name: &str| args.iter().position(|arg| arg == name);
+
+	if let Some(i) = flag("--snapshot") {
+		let (steps, path) = match (args.get(i + 1), args.get(i + 2)) {
+			(Some(steps), Some(path)) => match steps.parse::<usize>() {
+				Ok(steps) => (steps, path),
+				Err(_) => usage(),
+			},
+			_ => usage(),
+		};
+		let mut computer = computer;
+		let mut game = Game::new();
+
+		computer.set(0, 2);
+
+		for _ in 0..steps {
+			if computer
+				.step(&mut game)
+				.expect("Failed to run program")
+				.is_some()
+			{
+				break;
+			}
 		}
+
+		std::fs::write(path, intcode::save(&computer, &game))
+			.expect("Failed to write snapshot");
+
+		return;
 	}
-}
 
-struct Game {
-	tiles: HashMap<(i64, i64), Tile>,
-	score: i64,
-}
+	if let Some(i) = flag("--resume") {
+		let path = args.get(i + 1).unwrap_or_else(|| usage());
+		let snapshot =
+			std::fs::read_to_string(path).expect("Failed to read snapshot");
+		let (mut computer, mut game) =
+			intcode::restore::<Game>(&snapshot).expect("Invalid snapshot");
 
-impl Game {
-	pub fn new() -> Self {
-		Game {
-			tiles: HashMap::new(),
-			score: 0,
+		computer.run(&mut game).expect("Failed to run program");
+
+		println!("Score: {}", game.score);
+
+		return;
+	}
+
+	// Profiles a full game, optionally writing the folded call stacks out for
+	// a flamegraph
+	if let Some(i) = flag("--profile") {
+		let mut computer = computer;
+		let mut game = Game::new();
+		let mut profile = Profile::new();
+
+		computer.set(0, 2);
+		computer
+			.run_profiled(&mut game, &mut profile)
+			.expect("Failed to run program");
+
+		print!("{}", profile.report(&computer, 20));
+
+		if let Some(path) = args.get(i + 1) {
+			std::fs::write(path, profile.folded())
+				.expect("Failed to write stacks");
 		}
+
+		return;
 	}
 
-	pub fn count(&self, id: Tile) -> usize {
-		self.tiles.values().filter(|t| t == &&id).count()
+	// Prints every change to the score as the game writes it, long before it
+	// is sent out with the next frame
+	if flag("--scores").is_some() {
+		let mut computer = computer;
+
+		computer.set(0, 2);
+
+		let mut watcher = Watcher::new(computer);
+
+		watcher.hook(SCORE, Access::Write, |_, hit| {
+			println!("{:>6}: {} -> {}", hit.pos, hit.old, hit.new);
+			Action::Continue
+		});
+		watcher
+			.run(&mut Game::new())
+			.expect("Failed to run program");
+
+		return;
+	}
+
+	let mut game = Game::new();
+
+	computer
+		.clone()
+		.run(&mut game)
+		.expect("Failed to run program");
+
+	println!("Number of blocks: {}", game.count(Tile::Block));
+
+	let mut computer = Cached::from(computer);
+	let mut game = Game::new();
+
+	computer.set(0, 2);
+	computer.run(&mut game).expect("Failed to run program");
+
+	assert_eq!(game.count(Tile::Block), 0);
+
+	println!("Score: {}", game.score);
+}
+
+#[test]
+fn test_resume() {
+	let mut computer = Computer::parse(include_str!("../input.txt"));
+	let mut game = Game::new();
+
+	computer.set(0, 2);
+
+	for _ in 0..100_000 {
+		computer.step(&mut game).unwrap();
 	}
+
+	let snapshot = intcode::save(&computer, &game);
+	let (mut resumed, mut resumed_game) =
+		intcode::restore::<Game>(&snapshot).unwrap();
+
+	computer.run(&mut game).unwrap();
+	resumed.run(&mut resumed_game).unwrap();
+
+	assert_eq!(resumed_game.score, game.score);
+	assert_eq!(resumed_game.count(Tile::Block), 0);
 }
 
-fn main() {
-	let input = include_str!("../input.txt");
-	let nums = input
-		.trim()
-		.split(',')
-		.map(|n| n.parse::<i64>().expect("Failed to parse number"))
-		.collect::<Vec<_>>();
+#[test]
+fn test_score_cell() {
+	let mut computer = Computer::parse(include_str!("../input.txt"));
+	let mut game = Game::new();
 
-	let mut computer = Computer::new(nums.clone());
+	computer.set(0, 2);
 
-	computer.run();
+	let mut watcher = Watcher::new(computer);
 
-	println!("Number of blocks: {}", computer.game.count(Tile::Block));
+	watcher.watch(SCORE, Access::Write);
 
-	let mut computer = Computer::new(nums);
+	let hit = watcher.run(&mut game).unwrap().unwrap();
 
-	computer.opcodes.insert(0, 2);
-	computer.run();
+	assert_eq!(hit.old, 0);
+	assert!(hit.new > 0);
 
-	assert_eq!(computer.game.count(Tile::Block), 0);
+	while watcher.run(&mut game).unwrap().is_some() {}
 
-	println!("Score: {}", computer.game.score);
+	assert_eq!(game.score, watcher.computer.get(SCORE));
 }
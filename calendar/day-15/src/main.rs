@@ -1,205 +1,10 @@
 use std::collections::HashMap;
+use std::io::BufWriter;
+use std::panic::{self, AssertUnwindSafe};
 
-struct Computer {
-	base: i64,
-	pos: usize,
-	opcodes: HashMap<usize, i64>,
-	droid: Droid,
-	input: i64,
-}
-
-impl Computer {
-	pub fn new(nums: Vec<i64>) -> Self {
-		let mut opcodes = HashMap::new();
-
-		for (i, n) in nums.into_iter().enumerate() {
-			opcodes.insert(i, n);
-		}
-
-		Computer {
-			base: 0,
-			pos: 0,
-			opcodes,
-			droid: Droid::new(),
-			input: 0,
-		}
-	}
-
-	pub fn run(&mut self) {
-		loop {
-			if self.exec() {
-				break;
-			}
-		}
-	}
-
-	fn get(&self, i: usize) -> i64 {
-		self.opcodes.get(&i).cloned().unwrap_or(0)
-	}
-
-	fn index(&self, i: usize) -> usize {
-		let mode = self.get(self.pos) / i64::pow(10, i as u32 + 2);
-		let j = self.pos + i + 1;
-
-		match mode % 10 {
-			0 => self.get(j) as usize,
-			2 => (self.get(j) + self.base) as usize,
-			_ => j,
-		}
-	}
-
-	fn arg(&self, i: usize) -> i64 {
-		let j = self.index(i);
-
-		self.get(j)
-	}
-
-	fn op<F>(&mut self, f: F)
-	where
-		F: Fn(i64, i64) -> i64,
-	{
-		let x = self.arg(0);
-		let y = self.arg(1);
-		let j = self.index(2);
-
-		self.opcodes.insert(j, f(x, y));
-		self.pos += 4;
-	}
-
-	fn add(&mut self) {
-		self.op(|x, y| x + y);
-	}
-
-	fn multiply(&mut self) {
-		self.op(|x, y| x * y);
-	}
-
-	fn input(&mut self) -> bool {
-		let input = if !self
-			.droid
-			.screen
-			.contains_key(&(self.droid.pos.0, self.droid.pos.1 - 1))
-		{
-			self.droid.directions.push(1);
-			1
-		} else if !self
-			.droid
-			.screen
-			.contains_key(&(self.droid.pos.0, self.droid.pos.1 + 1))
-		{
-			self.droid.directions.push(2);
-			2
-		} else if !self
-			.droid
-			.screen
-			.contains_key(&(self.droid.pos.0 - 1, self.droid.pos.1))
-		{
-			self.droid.directions.push(3);
-			3
-		} else if !self
-			.droid
-			.screen
-			.contains_key(&(self.droid.pos.0 + 1, self.droid.pos.1))
-		{
-			self.droid.directions.push(4);
-			4
-		} else {
-			match self.droid.directions.pop() {
-				Some(1) => 2,
-				Some(2) => 1,
-				Some(3) => 4,
-				Some(4) => 3,
-				_ => return true,
-			}
-		};
-
-		let j = self.index(0);
-		self.droid.mv(input);
-		self.input = input;
-		self.opcodes.insert(j, input);
-		self.pos += 2;
-
-		false
-	}
-
-	fn output(&mut self) {
-		let j = self.index(0);
-		let output = self.get(j);
-
-		match output {
-			0 => {
-				self.droid.screen.insert(self.droid.pos, Tile::Wall);
-				self.droid.pos = self.droid.prev;
-				self.droid.directions.pop();
-			}
-			1 => {
-				self.droid.screen.insert(self.droid.pos, Tile::Empty);
-			}
-			2 => {
-				self.droid.distance = self.droid.directions.len();
-				self.droid.screen.insert(self.droid.pos, Tile::Oxygen);
-			}
-			_ => panic!(),
-		};
-
-		self.pos += 2;
-	}
-
-	fn increment_base(&mut self) {
-		let n = self.arg(0);
-
-		self.base += n;
-		self.pos += 2;
-	}
-
-	fn jump(&mut self, mode: bool) {
-		let x = self.arg(0);
-		let y = self.arg(1);
-
-		if (x != 0 && mode) || (x == 0 && !mode) {
-			self.pos = y as usize;
-		} else {
-			self.pos += 3;
-		}
-	}
-
-	fn compare(&mut self, mode: bool) {
-		let x = self.arg(0);
-		let y = self.arg(1);
-		let j = self.index(2);
-
-		let value = if (mode && x < y) || (!mode && x == y) {
-			1
-		} else {
-			0
-		};
-
-		self.opcodes.insert(j, value);
-		self.pos += 4;
-	}
-
-	fn exec(&mut self) -> bool {
-		match self.get(self.pos) % 100 {
-			1 => self.add(),
-			2 => self.multiply(),
-			3 => {
-				if self.input() {
-					return true;
-				}
-			}
-			4 => self.output(),
-			5 => self.jump(true),
-			6 => self.jump(false),
-			7 => self.compare(true),
-			8 => self.compare(false),
-			9 => self.increment_base(),
-			99 => return true,
-			n => panic!("Unexpected opcode: {}", n),
-		}
-
-		false
-	}
-}
+use intcode::{
+	Computer, Device, Halt, History, Line, Persist, SnapshotError, VmError,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Tile {
@@ -259,6 +64,119 @@ impl Droid {
 	}
 }
 
+impl Device for Droid {
+	fn send(&mut self) -> Option<i64> {
+		let input = if !self.screen.contains_key(&(self.pos.0, self.pos.1 - 1))
+		{
+			self.directions.push(1);
+			1
+		} else if !self.screen.contains_key(&(self.pos.0, self.pos.1 + 1)) {
+			self.directions.push(2);
+			2
+		} else if !self.screen.contains_key(&(self.pos.0 - 1, self.pos.1)) {
+			self.directions.push(3);
+			3
+		} else if !self.screen.contains_key(&(self.pos.0 + 1, self.pos.1)) {
+			self.directions.push(4);
+			4
+		} else {
+			match self.directions.pop() {
+				Some(1) => 2,
+				Some(2) => 1,
+				Some(3) => 4,
+				Some(4) => 3,
+				_ => return None,
+			}
+		};
+
+		self.mv(input);
+
+		Some(input)
+	}
+
+	fn receive(&mut self, n: i64) {
+		match n {
+			0 => {
+				self.screen.insert(self.pos, Tile::Wall);
+				self.pos = self.prev;
+				self.directions.pop();
+			}
+			1 => {
+				self.screen.insert(self.pos, Tile::Empty);
+			}
+			2 => {
+				self.distance = self.directions.len();
+				self.screen.insert(self.pos, Tile::Oxygen);
+			}
+			n => panic!("Unexpected status: {}", n),
+		};
+	}
+}
+
+impl Persist for Droid {
+	fn save(&self) -> String {
+		let mut lines = vec![
+			format!("pos {},{}", self.pos.0, self.pos.1),
+			format!("prev {},{}", self.prev.0, self.prev.1),
+			format!("distance {}", self.distance),
+			format!("directions {}", intcode::join_list(&self.directions)),
+		];
+
+		let mut tiles = self.screen.iter().collect::<Vec<_>>();
+
+		tiles.sort_by_key(|((x, y), _)| (*y, *x));
+
+		for ((x, y), tile) in tiles {
+			let id = match tile {
+				Tile::Wall => 0,
+				Tile::Empty => 1,
+				Tile::Oxygen => 2,
+				Tile::Unknown => continue,
+			};
+
+			lines.push(format!("tile {},{},{}", x, y, id));
+		}
+
+		lines.join("\n")
+	}
+
+	fn restore(state: &str) -> Result<Self, SnapshotError> {
+		let mut droid = Droid::new();
+
+		for line in state.lines().map(str::trim).filter(|l| !l.is_empty()) {
+			let mut parts = line.splitn(2, ' ');
+			let key = parts.next().unwrap_or("");
+			let values = intcode::parse_list(parts.next().unwrap_or(""))?;
+
+			match (key, values.as_slice()) {
+				("pos", [x, y]) => droid.pos = (*x, *y),
+				("prev", [x, y]) => droid.prev = (*x, *y),
+				("distance", [n]) if *n >= 0 => droid.distance = *n as usize,
+				("directions", directions) => {
+					droid.directions = directions.to_vec()
+				}
+				("tile", [x, y, 0]) => {
+					droid.screen.insert((*x, *y), Tile::Wall);
+				}
+				("tile", [x, y, 1]) => {
+					droid.screen.insert((*x, *y), Tile::Empty);
+				}
+				("tile", [x, y, 2]) => {
+					droid.screen.insert((*x, *y), Tile::Oxygen);
+				}
+				_ => {
+					return Err(SnapshotError(format!(
+						"Invalid line: {}",
+						line
+					)))
+				}
+			}
+		}
+
+		Ok(droid)
+	}
+}
+
 impl std::fmt::Display for Droid {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		let min_x = self
@@ -324,22 +242,74 @@ impl std::fmt::Display for Droid {
 	}
 }
 
+// The droid stops providing input once every reachable tile is known
+fn explored(result: Result<Halt, VmError>) {
+	match result {
+		Err(VmError::InputStarved { .. }) => {}
+		result => panic!("Unexpected result: {:?}", result),
+	}
+}
+
+// Steps the machine back over every instruction in its history, returning
+// them in the order they ran, followed by the one it stopped on
+fn rewind(computer: &mut Computer, history: &mut History) -> Vec<Line> {
+	let mut lines = vec![Line::decode(|i| computer.get(i), computer.pos)];
+
+	while let Some(addr) = history.undo(computer) {
+		lines.push(Line::decode(|i| computer.get(i), addr));
+	}
+
+	lines.reverse();
+	lines
+}
+
 fn main() {
 	let input = include_str!("../input.txt");
-	let nums = input
-		.trim()
-		.split(',')
-		.map(|n| n.parse::<i64>().expect("Failed to parse number"))
-		.collect::<Vec<_>>();
+	let mut computer = Computer::parse(input);
+	let mut droid = Droid::new();
+
+	let args = std::env::args().collect::<Vec<_>>();
+	let trace = args
+		.iter()
+		.position(|arg| arg == "--trace")
+		.and_then(|i| args.get(i + 1));
+
+	let window = args.iter().position(|arg| arg == "--rewind").map(|i| {
+		args.get(i + 1)
+			.map_or(20, |n| n.parse().expect("Invalid count"))
+	});
+
+	let result = if let Some(n) = window {
+		// Shows the last instructions before the droid panics on a status
+		let mut history = History::with_limit(n);
+		let result = panic::catch_unwind(AssertUnwindSafe(|| {
+			computer.run_logged(&mut droid, &mut history)
+		}));
+
+		match result {
+			Ok(result) => result,
+			Err(payload) => {
+				for line in rewind(&mut computer, &mut history) {
+					eprintln!("{:>6}: {}", line.addr(), line);
+				}
 
-	let mut computer = Computer::new(nums);
+				panic::resume_unwind(payload)
+			}
+		}
+	} else if let Some(path) = trace {
+		let file = std::fs::File::create(path).expect("Failed to create trace");
+
+		intcode::record(&mut computer, &mut droid, BufWriter::new(file))
+			.expect("Failed to write trace")
+	} else {
+		computer.run(&mut droid)
+	};
 
-	// Maze explored
-	computer.run();
+	explored(result);
 
-	println!("Distance: {}", computer.droid.distance);
+	println!("Distance: {}", droid.distance);
 
-	let mut screen = computer.droid.screen;
+	let mut screen = droid.screen;
 
 	// Flood fill!
 	let mut remaining = screen.values().filter(|t| t == &&Tile::Empty).count();
@@ -388,3 +358,52 @@ fn main() {
 		}
 	}
 }
+
+#[test]
+fn test_fork() {
+	let mut computer = Computer::parse(include_str!("../input.txt"));
+	let mut droid = Droid::new();
+
+	for _ in 0..20_000 {
+		computer.step(&mut droid).unwrap();
+	}
+
+	let snapshot = intcode::save(&computer, &droid);
+
+	explored(computer.run(&mut droid));
+
+	for _ in 0..2 {
+		let (mut fork, mut forked) =
+			intcode::restore::<Droid>(&snapshot).unwrap();
+
+		explored(fork.run(&mut forked));
+
+		assert_eq!(forked.distance, droid.distance);
+		assert_eq!(forked.screen, droid.screen);
+	}
+}
+
+#[test]
+fn test_rewind() {
+	// Moves north, then sends back a status the droid does not understand
+	let mut computer = Computer::parse("3,9,1001,9,4,10,4,10,99,0,0");
+	let mut history = History::new();
+	let mut droid = Droid::new();
+
+	let result = panic::catch_unwind(AssertUnwindSafe(|| {
+		computer.run_logged(&mut droid, &mut history)
+	}));
+
+	assert!(result.is_err());
+
+	let lines = rewind(&mut computer, &mut history)
+		.iter()
+		.map(|line| format!("{}: {}", line.addr(), line))
+		.collect::<Vec<_>>();
+
+	assert_eq!(
+		lines,
+		vec!["0: IN -> [9]", "2: ADD [9], #4 -> [10]", "6: OUT [10]"]
+	);
+	assert_eq!(computer.get(9), 0);
+}
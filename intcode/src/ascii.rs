@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+
+use crate::io::Io;
+
+#[cfg(test)]
+use crate::computer::{Computer, Halt};
+#[cfg(test)]
+use crate::error::VmError;
+#[cfg(test)]
+use crate::io::Buffer;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Chunk {
+	Text(String),
+	// An output too large to be a character
+	Value(i64),
+}
+
+// The character codes of a line of input, ending in a newline. Anything
+// past ASCII is refused, as a program could not tell its code from a value.
+pub fn encode(line: &str) -> Option<Vec<i64>> {
+	line.trim_end_matches(['\r', '\n'])
+		.chars()
+		.chain(std::iter::once('\n'))
+		.map(|c| match c.is_ascii() {
+			true => Some(c as i64),
+			false => None,
+		})
+		.collect()
+}
+
+// Splits outputs into runs of text and the values that can't be text
+pub fn decode(outputs: &[i64]) -> Vec<Chunk> {
+	let mut chunks = Vec::new();
+
+	for &n in outputs {
+		match (n, chunks.last_mut()) {
+			(0..=127, Some(Chunk::Text(text))) => text.push(n as u8 as char),
+			(0..=127, _) => chunks.push(Chunk::Text((n as u8 as char).into())),
+			_ => chunks.push(Chunk::Value(n)),
+		}
+	}
+
+	chunks
+}
+
+// Drives a text program from a terminal. Each time the program runs out of
+// input a line is read and fed to it, and it stops starving at the end of the
+// input, asking again for any line that isn't ASCII. Output is written as it
+// comes, with values that aren't characters on a line of their own.
+#[derive(Debug)]
+pub struct Terminal<R, W> {
+	reader: R,
+	writer: W,
+	inputs: VecDeque<i64>,
+	// Whether the last thing written ended a line
+	newline: bool,
+}
+
+impl<R: BufRead, W: Write> Terminal<R, W> {
+	pub fn new(reader: R, writer: W) -> Self {
+		Terminal {
+			reader,
+			writer,
+			inputs: VecDeque::new(),
+			newline: true,
+		}
+	}
+}
+
+impl Terminal<std::io::StdinLock<'static>, std::io::Stdout> {
+	pub fn stdio() -> Self {
+		Terminal::new(std::io::stdin().lock(), std::io::stdout())
+	}
+}
+
+impl<R: BufRead, W: Write> Io for Terminal<R, W> {
+	fn input(&mut self) -> Option<i64> {
+		while self.inputs.is_empty() {
+			let mut line = String::new();
+
+			let _ = self.writer.flush();
+
+			match self.reader.read_line(&mut line) {
+				Ok(0) | Err(_) => return None,
+				Ok(_) => match encode(&line) {
+					Some(codes) => self.inputs.extend(codes),
+					None => {
+						let _ = writeln!(self.writer, "Input must be ASCII");
+					}
+				},
+			}
+		}
+
+		self.inputs.pop_front()
+	}
+
+	fn output(&mut self, n: i64) {
+		let _ = match n {
+			0..=127 => self.writer.write_all(&[n as u8]),
+			_ if self.newline => writeln!(self.writer, "{}", n),
+			_ => writeln!(self.writer, "\n{}", n),
+		};
+
+		self.newline = !(0..=127).contains(&n) || n == 10;
+	}
+}
+
+#[test]
+fn test_ascii() {
+	assert_eq!(
+		encode("NOT A J\n"),
+		Some(vec![78, 79, 84, 32, 65, 32, 74, 10])
+	);
+	assert_eq!(encode("caf\u{e9}"), None);
+	assert_eq!(
+		decode(&[72, 105, 10, 19_360_724, 33]),
+		vec![
+			Chunk::Text("Hi\n".to_string()),
+			Chunk::Value(19_360_724),
+			Chunk::Text("!".to_string()),
+		]
+	);
+
+	// Echoes a line back, then outputs 1000 plus its length
+	let program =
+		"3,20,4,20,1001,21,1,21,1008,20,10,22,1006,22,0,4,21,99,0,0,0,1000,0";
+	let mut io = Buffer::new(encode("hello").unwrap());
+
+	assert_eq!(Computer::parse(program).run(&mut io), Ok(Halt::Exit));
+	assert_eq!(
+		decode(&io.outputs),
+		vec![Chunk::Text("hello\n".to_string()), Chunk::Value(1006)]
+	);
+}
+
+#[test]
+fn test_terminal() {
+	// Echoes every line until the input runs out
+	let program = "3,7,4,7,1105,1,0,0";
+	let mut out = Vec::new();
+	let mut terminal = Terminal::new("one\nt\u{e9}o\ntwo".as_bytes(), &mut out);
+
+	assert_eq!(
+		Computer::parse(program).run(&mut terminal),
+		Err(VmError::InputStarved { addr: 0 })
+	);
+	assert_eq!(
+		String::from_utf8(out).unwrap(),
+		"one\nInput must be ASCII\ntwo\n"
+	);
+
+	let mut out = Vec::new();
+	let mut terminal = Terminal::new("".as_bytes(), &mut out);
+
+	assert_eq!(
+		Computer::parse("104,72,104,200,104,300,104,10,104,400,99")
+			.run(&mut terminal),
+		Ok(Halt::Exit)
+	);
+	assert_eq!(String::from_utf8(out).unwrap(), "H\n200\n300\n\n400\n");
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::instruction::{Instruction, Mode, Opcode};
+
+#[cfg(test)]
+use crate::disasm::disassemble;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmError {
+	pub line: usize,
+	pub message: String,
+}
+
+impl AsmError {
+	fn new(line: usize, message: impl Into<String>) -> Self {
+		AsmError {
+			line,
+			message: message.into(),
+		}
+	}
+}
+
+impl fmt::Display for AsmError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}: {}", self.line, self.message)
+	}
+}
+
+impl std::error::Error for AsmError {}
+
+enum Value {
+	Number(i64),
+	Label(String),
+}
+
+impl Value {
+	fn parse(s: &str, line: usize) -> Result<Self, AsmError> {
+		if let Ok(n) = s.parse::<i64>() {
+			return Ok(Value::Number(n));
+		}
+
+		if is_label(s) {
+			return Ok(Value::Label(s.to_string()));
+		}
+
+		Err(AsmError::new(line, format!("Invalid value: {}", s)))
+	}
+
+	fn resolve(
+		&self,
+		labels: &HashMap<String, usize>,
+		line: usize,
+	) -> Result<i64, AsmError> {
+		match self {
+			Value::Number(n) => Ok(*n),
+			Value::Label(label) => {
+				labels.get(label).map(|addr| *addr as i64).ok_or_else(|| {
+					AsmError::new(line, format!("Unknown label: {}", label))
+				})
+			}
+		}
+	}
+}
+
+fn is_label(s: &str) -> bool {
+	let mut chars = s.chars();
+
+	match chars.next() {
+		Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+		_ => return false,
+	}
+
+	s != "rb" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_operand(s: &str, line: usize) -> Result<(Mode, Value), AsmError> {
+	if let Some(s) = s.strip_prefix('#') {
+		return Ok((Mode::Immediate, Value::parse(s.trim(), line)?));
+	}
+
+	if let Some(s) = s.strip_prefix('[') {
+		let s = s.strip_suffix(']').ok_or_else(|| {
+			AsmError::new(line, format!("Unterminated operand: [{}", s))
+		})?;
+
+		return Ok((Mode::Position, Value::parse(s.trim(), line)?));
+	}
+
+	if let Some(s) = s.strip_prefix("rb") {
+		let s = s.trim();
+		let value = match s.chars().next() {
+			None => Value::Number(0),
+			Some('+') => Value::parse(s[1..].trim(), line)?,
+			// Parsed with its sign, so that `rb-9223372036854775808` from the
+			// disassembler comes back as `i64::MIN`
+			Some('-') => match format!("-{}", s[1..].trim()).parse::<i64>() {
+				Ok(n) => Value::Number(n),
+				Err(_) => match Value::parse(s[1..].trim(), line)? {
+					Value::Number(n) => match n.checked_neg() {
+						Some(n) => Value::Number(n),
+						None => {
+							return Err(AsmError::new(
+								line,
+								format!(
+									"Relative offset out of range: rb{}",
+									s
+								),
+							))
+						}
+					},
+					Value::Label(_) => {
+						return Err(AsmError::new(
+							line,
+							format!("Cannot negate a label: rb{}", s),
+						))
+					}
+				},
+			},
+			_ => {
+				return Err(AsmError::new(
+					line,
+					format!("Invalid relative operand: rb{}", s),
+				))
+			}
+		};
+
+		return Ok((Mode::Relative, value));
+	}
+
+	Err(AsmError::new(line, format!("Invalid operand: {}", s)))
+}
+
+pub fn assemble(input: &str) -> Result<Vec<i64>, AsmError> {
+	let mut labels = HashMap::new();
+	let mut words = Vec::new();
+
+	for (i, line) in input.lines().enumerate() {
+		let line_no = i + 1;
+		let mut line = line.split(';').next().unwrap_or("").trim();
+
+		while let Some(n) = line.find(':') {
+			let label = line[..n].trim();
+
+			if let Ok(addr) = label.parse::<usize>() {
+				if addr != words.len() {
+					return Err(AsmError::new(
+						line_no,
+						format!(
+							"Expected address {}, found {}",
+							words.len(),
+							addr
+						),
+					));
+				}
+			} else if is_label(label) {
+				if labels.insert(label.to_string(), words.len()).is_some() {
+					return Err(AsmError::new(
+						line_no,
+						format!("Duplicate label: {}", label),
+					));
+				}
+			} else {
+				break;
+			}
+
+			line = line[(n + 1)..].trim();
+		}
+
+		if line.is_empty() {
+			continue;
+		}
+
+		let (name, rest) = match line.find(char::is_whitespace) {
+			Some(n) => (&line[..n], line[n..].trim()),
+			None => (line, ""),
+		};
+
+		if name == ".data" {
+			for value in rest.split(',').map(str::trim) {
+				words.push((line_no, Value::parse(value, line_no)?));
+			}
+
+			continue;
+		}
+
+		let opcode = Opcode::from_mnemonic(name).ok_or_else(|| {
+			AsmError::new(line_no, format!("Unknown mnemonic: {}", name))
+		})?;
+
+		let operands = rest
+			.replace("->", ",")
+			.split(',')
+			.map(str::trim)
+			.filter(|s| !s.is_empty())
+			.map(|s| parse_operand(s, line_no))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		if operands.len() != opcode.arity() {
+			return Err(AsmError::new(
+				line_no,
+				format!(
+					"{} expects {} operands, found {}",
+					opcode.mnemonic(),
+					opcode.arity(),
+					operands.len()
+				),
+			));
+		}
+
+		let mut modes = [Mode::Position; 3];
+
+		for (i, (mode, _)) in operands.iter().enumerate() {
+			modes[i] = *mode;
+		}
+
+		let code = Instruction { opcode, modes }.encode();
+
+		words.push((line_no, Value::Number(code)));
+		words.extend(operands.into_iter().map(|(_, value)| (line_no, value)));
+	}
+
+	words
+		.into_iter()
+		.map(|(line, value)| value.resolve(&labels, line))
+		.collect()
+}
+
+#[test]
+fn test_assemble() {
+	let input = "
+		; Echo a single input
+		start:
+			IN -> [value]
+			OUT [value]
+			HLT
+		value: .data 0
+	";
+
+	assert_eq!(assemble(input), Ok(vec![3, 5, 4, 5, 99, 0]));
+}
+
+#[test]
+fn test_assemble_modes() {
+	let input = "
+			ARB #3
+			ADD #12, [1] -> rb+0
+			OUT rb-3
+			JT #1, #end
+			.data 1, 2
+		end:
+			HLT
+	";
+
+	let expected =
+		vec![109, 3, 20101, 12, 1, 0, 204, -3, 1105, 1, 13, 1, 2, 99];
+
+	assert_eq!(assemble(input), Ok(expected));
+}
+
+#[test]
+fn test_assemble_errors() {
+	assert_eq!(assemble("NOP").unwrap_err().line, 1);
+	assert_eq!(assemble("\nADD #1, #2").unwrap_err().line, 2);
+	assert_eq!(assemble("JT #1, #nowhere").unwrap_err().line, 1);
+	assert_eq!(assemble("a: HLT\na: HLT").unwrap_err().line, 2);
+	assert_eq!(assemble("1: HLT").unwrap_err().line, 1);
+	assert_eq!(assemble("OUT rb--9223372036854775808").unwrap_err().line, 1);
+	assert_eq!(
+		assemble("OUT rb-9223372036854775808"),
+		Ok(vec![204, i64::MIN])
+	);
+}
+
+#[test]
+fn test_round_trip() {
+	let input = include_str!("../../calendar/day-15/input.txt");
+	let nums = crate::parse(input);
+	let listing = disassemble(&nums).to_string();
+
+	assert_eq!(assemble(&listing), Ok(nums));
+}
+
+#[test]
+fn test_round_trip_stray_modes() {
+	let nums = vec![1101, 2, 3, 9, 1004, 9, 1099, 0, 0, 0];
+	let listing = disassemble(&nums).to_string();
+
+	assert!(listing.contains("     6: .data 1099"));
+	assert_eq!(assemble(&listing), Ok(nums));
+}
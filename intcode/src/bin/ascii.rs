@@ -0,0 +1,16 @@
+fn main() {
+	let path = std::env::args().nth(1).expect("Usage: ascii <program.txt>");
+
+	let input = std::fs::read_to_string(path).expect("Failed to read program");
+	let mut computer = intcode::Computer::parse(&input);
+	let mut terminal = intcode::Terminal::stdio();
+
+	match computer.run(&mut terminal) {
+		Ok(_) => {}
+		Err(intcode::VmError::InputStarved { .. }) => {}
+		Err(err) => {
+			eprintln!("{}", err);
+			std::process::exit(1);
+		}
+	}
+}
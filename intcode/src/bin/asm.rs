@@ -0,0 +1,21 @@
+fn main() {
+	let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+	let list = args.iter().any(|arg| arg == "--list");
+
+	args.retain(|arg| arg != "--list");
+
+	let path = args.pop().expect("Usage: asm [--list] <program.asm>");
+	let input = std::fs::read_to_string(path).expect("Failed to read source");
+	let nums = intcode::assemble(&input).unwrap_or_else(|err| {
+		eprintln!("{}", err);
+		std::process::exit(1);
+	});
+
+	if list {
+		print!("{}", intcode::disassemble(&nums));
+	} else {
+		let nums = nums.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+		println!("{}", nums.join(","));
+	}
+}
@@ -0,0 +1,184 @@
+use std::time::{Duration, Instant};
+
+use intcode::{
+	Buffer, Cached, Computer, Dense, Device, Halt, Io, Memory, Sparse, VmError,
+};
+
+// Plays the day 13 arcade game, tracking only what the joystick needs so that
+// the time is spent in the VM rather than the device.
+#[derive(Default)]
+struct Arcade {
+	output: Vec<i64>,
+	ball: i64,
+	paddle: i64,
+	score: i64,
+}
+
+impl Device for Arcade {
+	fn send(&mut self) -> Option<i64> {
+		Some((self.ball - self.paddle).signum())
+	}
+
+	fn receive(&mut self, n: i64) {
+		self.output.push(n);
+
+		if let [x, y, id] = self.output[..] {
+			match (x, y, id) {
+				(-1, 0, score) => self.score = score,
+				(x, _, 3) => self.paddle = x,
+				(x, _, 4) => self.ball = x,
+				_ => {}
+			}
+
+			self.output.clear();
+		}
+	}
+}
+
+trait Engine: Clone {
+	fn set(&mut self, i: usize, n: i64);
+	fn run<T: Io>(&mut self, io: &mut T) -> Result<Halt, VmError>;
+}
+
+impl<M: Memory> Engine for Computer<M> {
+	fn set(&mut self, i: usize, n: i64) {
+		Computer::set(self, i, n)
+	}
+
+	fn run<T: Io>(&mut self, io: &mut T) -> Result<Halt, VmError> {
+		Computer::run(self, io)
+	}
+}
+
+impl<M: Memory> Engine for Cached<M> {
+	fn set(&mut self, i: usize, n: i64) {
+		Cached::set(self, i, n)
+	}
+
+	fn run<T: Io>(&mut self, io: &mut T) -> Result<Halt, VmError> {
+		Cached::run(self, io)
+	}
+}
+
+fn play<E: Engine>(engine: &E) -> i64 {
+	let mut engine = engine.clone();
+	let mut arcade = Arcade::default();
+
+	engine.set(0, 2);
+	engine.run(&mut arcade).expect("Failed to run program");
+	arcade.score
+}
+
+fn permutations(phases: &[i64]) -> Vec<Vec<i64>> {
+	if phases.len() <= 1 {
+		return vec![phases.to_vec()];
+	}
+
+	(0..phases.len())
+		.flat_map(|i| {
+			let mut rest = phases.to_vec();
+			let phase = rest.remove(i);
+
+			permutations(&rest).into_iter().map(move |mut p| {
+				p.insert(0, phase);
+				p
+			})
+		})
+		.collect()
+}
+
+fn feedback<E: Engine>(engine: &E) -> i64 {
+	let mut max = 0;
+
+	for phases in permutations(&[5, 6, 7, 8, 9]) {
+		let mut amps = phases
+			.iter()
+			.map(|phase| (engine.clone(), Buffer::new(vec![*phase])))
+			.collect::<Vec<_>>();
+
+		let mut signal = vec![0];
+		let mut halted = false;
+
+		while !halted {
+			for (amp, io) in amps.iter_mut() {
+				io.inputs.extend(signal);
+
+				halted = match amp.run(io) {
+					Ok(Halt::Exit) => true,
+					Err(VmError::InputStarved { .. }) => false,
+					Err(err) => panic!("{}", err),
+				};
+
+				signal = io.drain();
+			}
+		}
+
+		max = max.max(*signal.last().expect("Expected an output"));
+	}
+
+	max
+}
+
+fn bench<F>(name: &str, runs: u32, mut f: F) -> Duration
+where
+	F: FnMut() -> i64,
+{
+	let start = Instant::now();
+	let mut result = None;
+
+	for _ in 0..runs {
+		result = Some(f());
+	}
+
+	let elapsed = start.elapsed() / runs;
+
+	println!(
+		"{:>8}: {:>10.2?} per run (result {:?})",
+		name, elapsed, result
+	);
+
+	elapsed
+}
+
+fn speedup(from: (&str, Duration), to: (&str, Duration)) {
+	println!(
+		"Speedup, {} to {}: {:.2}x",
+		from.0,
+		to.0,
+		from.1.as_secs_f64() / to.1.as_secs_f64()
+	);
+}
+
+fn main() {
+	let runs = std::env::args()
+		.nth(1)
+		.map(|n| n.parse::<u32>().expect("Invalid run count"))
+		.unwrap_or(10);
+
+	let input = include_str!("../../../calendar/day-13/input.txt");
+	let nums = intcode::parse(input);
+
+	let sparse = Computer::with_memory(Sparse::from(nums.clone()));
+	let dense = Computer::with_memory(Dense::from(nums));
+	let cached = Cached::from(dense.clone());
+
+	println!("Day 13, full game");
+
+	let sparse = bench("sparse", runs, || play(&sparse));
+	let dense = bench("dense", runs, || play(&dense));
+	let cached = bench("cached", runs, || play(&cached));
+
+	speedup(("sparse", sparse), ("dense", dense));
+	speedup(("dense", dense), ("cached", cached));
+
+	let input = include_str!("../../../calendar/day-07/input.txt");
+	let computer = Computer::parse(input);
+	let cached = Cached::from(computer.clone());
+
+	println!("Day 07, feedback permutations");
+
+	let dense = bench("dense", runs, || feedback(&computer));
+	let cached = bench("cached", runs, || feedback(&cached));
+
+	speedup(("dense", dense), ("cached", cached));
+}
@@ -0,0 +1,10 @@
+fn main() {
+	let path = std::env::args()
+		.nth(1)
+		.expect("Usage: disasm <program.txt>");
+
+	let input = std::fs::read_to_string(path).expect("Failed to read program");
+	let nums = intcode::parse(&input);
+
+	print!("{}", intcode::disassemble(&nums));
+}
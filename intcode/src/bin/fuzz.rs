@@ -0,0 +1,20 @@
+// Checks every engine against the reference interpreter on random programs
+fn main() {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	let arg = |i: usize, default: u64| {
+		args.get(i)
+			.map_or(default, |n| n.parse().expect("Usage: fuzz [runs] [seed]"))
+	};
+
+	match intcode::fuzz(arg(1, 0), arg(0, 10_000), 10_000) {
+		Ok(stopped) => println!("Compared {} programs that stopped", stopped),
+		Err(difference) => {
+			println!("Engine: {}", difference.engine);
+			println!("Inputs: {:?}", difference.inputs);
+			println!("Expected: {:?}", difference.expected);
+			println!("Actual: {:?}", difference.actual);
+			print!("{}", intcode::disassemble(&difference.program));
+			std::process::exit(1);
+		}
+	}
+}
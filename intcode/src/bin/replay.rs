@@ -0,0 +1,23 @@
+use std::io::BufReader;
+
+fn main() {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+	if args.len() != 2 {
+		eprintln!("Usage: replay <program.txt> <trace.txt>");
+		std::process::exit(1);
+	}
+
+	let input =
+		std::fs::read_to_string(&args[0]).expect("Failed to read program");
+	let trace = std::fs::File::open(&args[1]).expect("Failed to read trace");
+	let mut computer = intcode::Computer::parse(&input);
+
+	match intcode::replay(&mut computer, BufReader::new(trace)) {
+		Ok(halt) => println!("Trace matches, stopped with {:?}", halt),
+		Err(err) => {
+			eprintln!("{}", err);
+			std::process::exit(1);
+		}
+	}
+}
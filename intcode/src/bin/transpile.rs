@@ -0,0 +1,10 @@
+fn main() {
+	let path = std::env::args()
+		.nth(1)
+		.expect("Usage: transpile <program.txt>");
+
+	let input = std::fs::read_to_string(path).expect("Failed to read program");
+	let nums = intcode::parse(&input);
+
+	print!("{}", intcode::transpile(&nums));
+}
@@ -0,0 +1,282 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::computer::{Computer, Halt};
+use crate::error::VmError;
+use crate::instruction::{Instruction, Opcode};
+use crate::io::Io;
+use crate::memory::Memory;
+
+#[cfg(test)]
+use crate::io::Buffer;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+	Halt(Halt),
+	// The step budget ran out first
+	OutOfSteps,
+	// The machine came back to a state it had already been in without any
+	// input or output since, so it would go round forever. These are the
+	// addresses of the instructions in one trip round, in order.
+	Loop(Vec<usize>),
+}
+
+// Memory is hashed as the XOR of a hash of every non-zero cell, so that a
+// write only has to swap the hash of one cell for another
+fn cell(addr: usize, value: i64) -> u64 {
+	if value == 0 {
+		return 0;
+	}
+
+	let mut hasher = DefaultHasher::new();
+
+	(addr, value).hash(&mut hasher);
+	hasher.finish()
+}
+
+fn state(memory: u64, pos: usize, base: i64) -> u64 {
+	let mut hasher = DefaultHasher::new();
+
+	(memory, pos, base).hash(&mut hasher);
+	hasher.finish()
+}
+
+#[derive(Clone, Debug, Default)]
+struct Detector {
+	memory: u64,
+	seen: HashMap<u64, usize>,
+	trail: Vec<usize>,
+	// The state the last run left the machine in
+	left: Option<u64>,
+}
+
+// Limits on how long `run_budgeted` runs for. The loop detector remembers the
+// machine it was used on, so it shouldn't be shared.
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+	steps: Option<u64>,
+	used: u64,
+	detector: Option<Detector>,
+}
+
+impl Budget {
+	pub fn new() -> Self {
+		Budget::default()
+	}
+
+	// Stops after this many more instructions
+	pub fn steps(&mut self, steps: u64) -> &mut Self {
+		self.steps = Some(self.used + steps);
+		self
+	}
+
+	// Hashes the whole state before every instruction to spot a loop. A hash
+	// collision could report a loop that isn't one, which at 64 bits is not
+	// worth checking for. One hash is kept for every instruction since the
+	// last input or output, so a program that never repeats and never does
+	// any I/O grows that without bound unless `steps` also limits it.
+	//
+	// Each run hashes the whole memory again before it starts. If the machine
+	// is not in the state the last run left it in, because the caller set
+	// memory, restored a snapshot or stepped back in between, the states
+	// seen so far are forgotten, as they no longer lead to one another.
+	pub fn detect_loops(&mut self) -> &mut Self {
+		self.detector = Some(Detector::default());
+		self
+	}
+
+	pub fn used(&self) -> u64 {
+		self.used
+	}
+}
+
+impl<M: Memory> Computer<M> {
+	pub fn run_budgeted<T: Io>(
+		&mut self,
+		io: &mut T,
+		budget: &mut Budget,
+	) -> Result<Outcome, VmError> {
+		if let Some(detector) = budget.detector.as_mut() {
+			let memory = self
+				.cells()
+				.into_iter()
+				.fold(0, |hash, (i, n)| hash ^ cell(i, n));
+
+			if detector.left != Some(state(memory, self.pos, self.base)) {
+				detector.seen.clear();
+				detector.trail.clear();
+			}
+
+			detector.memory = memory;
+		}
+
+		let outcome = self.spend(io, budget);
+
+		if let Some(detector) = budget.detector.as_mut() {
+			detector.left = Some(state(detector.memory, self.pos, self.base));
+		}
+
+		outcome
+	}
+
+	fn spend<T: Io>(
+		&mut self,
+		io: &mut T,
+		budget: &mut Budget,
+	) -> Result<Outcome, VmError> {
+		loop {
+			if budget.steps.is_some_and(|steps| budget.used >= steps) {
+				return Ok(Outcome::OutOfSteps);
+			}
+
+			let detector = match budget.detector.as_mut() {
+				Some(detector) => detector,
+				None => match self.step(io)? {
+					Some(halt) => return Ok(Outcome::Halt(halt)),
+					None => {
+						budget.used += 1;
+						continue;
+					}
+				},
+			};
+
+			let pos = self.pos;
+			let memory = detector.memory;
+			let state = state(memory, pos, self.base);
+
+			if let Some(start) = detector.seen.get(&state) {
+				return Ok(Outcome::Loop(detector.trail[*start..].to_vec()));
+			}
+
+			let instruction = Instruction::decode(self.get(pos), pos)?;
+			let write = self.write_target().map(|j| (j, self.get(j)));
+
+			if let Some(halt) = self.step(io)? {
+				return Ok(Outcome::Halt(halt));
+			}
+
+			// Only once the step went through, as one that failed, say for
+			// want of input, may be tried again from the same state
+			detector.seen.insert(state, detector.trail.len());
+			budget.used += 1;
+			detector.memory = match write {
+				Some((j, old)) => memory ^ cell(j, old) ^ cell(j, self.get(j)),
+				None => memory,
+			};
+
+			match instruction.opcode {
+				Opcode::Input | Opcode::Output => {
+					detector.seen.clear();
+					detector.trail.clear();
+				}
+				_ => detector.trail.push(pos),
+			}
+		}
+	}
+}
+
+#[test]
+fn test_budget() {
+	// Counts [8] up forever
+	let mut computer = Computer::parse("1001,8,1,8,1105,1,0,99,0");
+	let mut budget = Budget::new();
+
+	budget.steps(100);
+
+	assert_eq!(
+		computer.run_budgeted(&mut Buffer::default(), &mut budget),
+		Ok(Outcome::OutOfSteps)
+	);
+	assert_eq!(computer.get(8), 50);
+
+	// Memory never repeats, so this is not a loop
+	budget.steps(100).detect_loops();
+
+	assert_eq!(
+		computer.run_budgeted(&mut Buffer::default(), &mut budget),
+		Ok(Outcome::OutOfSteps)
+	);
+	assert_eq!(budget.used(), 200);
+
+	let mut computer = Computer::parse("1101,0,0,5,99,0");
+
+	assert_eq!(
+		computer.run_budgeted(&mut Buffer::default(), &mut Budget::new()),
+		Ok(Outcome::Halt(Halt::Exit))
+	);
+}
+
+#[test]
+fn test_loops() {
+	// Flips [9] between 0 and 1 forever
+	let mut computer = Computer::parse("1008,9,0,9,1105,1,0,99,0,0");
+	let mut budget = Budget::new();
+
+	budget.detect_loops();
+
+	assert_eq!(
+		computer.run_budgeted(&mut Buffer::default(), &mut budget),
+		Ok(Outcome::Loop(vec![0, 4, 0, 4]))
+	);
+	assert_eq!(budget.used(), 4);
+
+	// Output in the loop keeps it from counting as one
+	let mut computer = Computer::parse("4,0,1105,1,0");
+	let mut io = Buffer::default();
+	let mut budget = Budget::new();
+
+	budget.steps(10).detect_loops();
+
+	assert_eq!(
+		computer.run_budgeted(&mut io, &mut budget),
+		Ok(Outcome::OutOfSteps)
+	);
+	assert_eq!(io.outputs, vec![4; 5]);
+}
+
+#[test]
+fn test_starved() {
+	let mut computer = Computer::parse("3,5,4,5,99,0");
+	let mut io = Buffer::default();
+	let mut budget = Budget::new();
+
+	budget.detect_loops();
+
+	assert_eq!(
+		computer.run_budgeted(&mut io, &mut budget),
+		Err(VmError::InputStarved { addr: 0 })
+	);
+
+	io.push(7);
+
+	assert_eq!(
+		computer.run_budgeted(&mut io, &mut budget),
+		Ok(Outcome::Halt(Halt::Exit))
+	);
+	assert_eq!(io.outputs, vec![7]);
+}
+
+#[test]
+fn test_changed_between_runs() {
+	// Counts [8] up forever
+	let mut computer = Computer::parse("1001,8,1,8,1105,1,0,99,0");
+	let mut budget = Budget::new();
+
+	budget.steps(2).detect_loops();
+
+	assert_eq!(
+		computer.run_budgeted(&mut Buffer::default(), &mut budget),
+		Ok(Outcome::OutOfSteps)
+	);
+
+	// Setting it back to where it started is not a loop it ran into
+	computer.set(8, 0);
+	budget.steps(10);
+
+	assert_eq!(
+		computer.run_budgeted(&mut Buffer::default(), &mut budget),
+		Ok(Outcome::OutOfSteps)
+	);
+	assert_eq!(computer.get(8), 5);
+}
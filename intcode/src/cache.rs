@@ -0,0 +1,328 @@
+use std::sync::Arc;
+
+use crate::computer::{address, Computer, Halt};
+use crate::error::VmError;
+use crate::instruction::{Instruction, Mode, Opcode};
+use crate::io::Io;
+use crate::memory::{Dense, Memory};
+
+#[cfg(test)]
+use crate::io::Buffer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operand {
+	Position(i64),
+	Immediate(i64),
+	Relative(i64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+	Add(Operand, Operand, Operand),
+	Multiply(Operand, Operand, Operand),
+	Input(Operand),
+	Output(Operand),
+	JumpTrue(Operand, Operand),
+	JumpFalse(Operand, Operand),
+	LessThan(Operand, Operand, Operand),
+	Equals(Operand, Operand, Operand),
+	IncrementBase(Operand),
+	Halt,
+}
+
+// Runs a computer from a cache of decoded instructions. A write to memory
+// marks the cached instructions that overlap it as stale, and a stale
+// instruction is decoded again before it runs, so self-modifying programs
+// behave exactly as they do on `Computer`.
+//
+// The whole program is decoded up front and the cache is shared between
+// clones, so copies of a fresh machine, like the amplifiers of day 7, start
+// warm. Only a machine that owns its cache updates it in place. The cache
+// covers the program and never grows, so instructions run from past its end
+// are decoded every time.
+//
+// This is not the several-fold speedup it was meant to be. On the `bench`
+// binary it runs the day 13 game about 1.9x faster than `Computer<Dense>`,
+// and day 7 only 1.1x to 1.3x, since that is mostly cloning machines and
+// passing signals between them. Operands are still resolved one at a time
+// on every step, which is where the rest of the time goes.
+#[derive(Clone, Debug)]
+pub struct Cached<M: Memory = Dense> {
+	computer: Computer<M>,
+	ops: Arc<Vec<Option<Op>>>,
+	stale: Vec<u64>,
+}
+
+impl<M: Memory> From<Computer<M>> for Cached<M> {
+	fn from(computer: Computer<M>) -> Self {
+		// The cells from 0 up to the first gap, which is at least the whole
+		// program. Anything written far past it stays out of the cache.
+		let len = computer
+			.cells()
+			.iter()
+			.enumerate()
+			.take_while(|(i, (j, _))| i == j)
+			.count();
+		let ops = (0..len)
+			.map(|pos| Cached::decode(&computer, pos).ok())
+			.collect::<Vec<_>>();
+
+		Cached {
+			computer,
+			stale: vec![0; len.div_ceil(64)],
+			ops: Arc::new(ops),
+		}
+	}
+}
+
+impl<M: Memory> Cached<M> {
+	pub fn computer(&self) -> &Computer<M> {
+		&self.computer
+	}
+
+	pub fn into_inner(self) -> Computer<M> {
+		self.computer
+	}
+
+	pub fn get(&self, i: usize) -> i64 {
+		self.computer.get(i)
+	}
+
+	pub fn set(&mut self, i: usize, n: i64) {
+		self.computer.set(i, n);
+
+		// An instruction is at most 4 cells long
+		for j in i.saturating_sub(3)..=i.min(self.ops.len()) {
+			if let Some(bits) = self.stale.get_mut(j / 64) {
+				*bits |= 1 << (j % 64);
+			}
+		}
+	}
+
+	fn is_stale(&self, pos: usize) -> bool {
+		self.stale[pos / 64] & (1 << (pos % 64)) != 0
+	}
+
+	fn refresh(&mut self, pos: usize) -> Result<Op, VmError> {
+		let op = Cached::decode(&self.computer, pos)?;
+
+		if let Some(ops) = Arc::get_mut(&mut self.ops) {
+			if let Some(cached) = ops.get_mut(pos) {
+				*cached = Some(op);
+				self.stale[pos / 64] &= !(1 << (pos % 64));
+			}
+		}
+
+		Ok(op)
+	}
+
+	pub fn run<T: Io>(&mut self, io: &mut T) -> Result<Halt, VmError> {
+		loop {
+			if let Some(halt) = self.step(io)? {
+				return Ok(halt);
+			}
+		}
+	}
+
+	pub fn step<T: Io>(&mut self, io: &mut T) -> Result<Option<Halt>, VmError> {
+		let pos = self.computer.pos;
+		let op = match self.ops.get(pos) {
+			Some(Some(op)) if !self.is_stale(pos) => *op,
+			_ => self.refresh(pos)?,
+		};
+
+		match op {
+			Op::Add(x, y, z) => self.op(x, y, z, i64::checked_add)?,
+			Op::Multiply(x, y, z) => self.op(x, y, z, i64::checked_mul)?,
+			Op::Input(x) => {
+				let j = self.target(x)?;
+				let input =
+					io.input().ok_or(VmError::InputStarved { addr: pos })?;
+
+				self.set(j, input);
+				self.computer.pos += 2;
+			}
+			Op::Output(x) => {
+				io.output(self.arg(x)?);
+				self.computer.pos += 2;
+			}
+			Op::JumpTrue(x, y) => self.jump(x, y, true)?,
+			Op::JumpFalse(x, y) => self.jump(x, y, false)?,
+			Op::LessThan(x, y, z) => {
+				self.op(x, y, z, |x, y| Some((x < y) as i64))?
+			}
+			Op::Equals(x, y, z) => {
+				self.op(x, y, z, |x, y| Some((x == y) as i64))?
+			}
+			Op::IncrementBase(x) => {
+				self.computer.base = self
+					.computer
+					.base
+					.checked_add(self.arg(x)?)
+					.ok_or(VmError::Overflow { addr: pos })?;
+				self.computer.pos += 2;
+			}
+			Op::Halt => return Ok(Some(Halt::Exit)),
+		}
+
+		Ok(None)
+	}
+
+	fn decode(computer: &Computer<M>, pos: usize) -> Result<Op, VmError> {
+		let instruction = Instruction::decode(computer.get(pos), pos)?;
+		let operand = |i: usize| {
+			let n = computer.get(pos + i + 1);
+
+			match instruction.modes[i] {
+				Mode::Position => Operand::Position(n),
+				Mode::Immediate => Operand::Immediate(n),
+				Mode::Relative => Operand::Relative(n),
+			}
+		};
+
+		let op = match instruction.opcode {
+			Opcode::Add => Op::Add(operand(0), operand(1), operand(2)),
+			Opcode::Multiply => {
+				Op::Multiply(operand(0), operand(1), operand(2))
+			}
+			Opcode::Input => Op::Input(operand(0)),
+			Opcode::Output => Op::Output(operand(0)),
+			Opcode::JumpTrue => Op::JumpTrue(operand(0), operand(1)),
+			Opcode::JumpFalse => Op::JumpFalse(operand(0), operand(1)),
+			Opcode::LessThan => {
+				Op::LessThan(operand(0), operand(1), operand(2))
+			}
+			Opcode::Equals => Op::Equals(operand(0), operand(1), operand(2)),
+			Opcode::IncrementBase => Op::IncrementBase(operand(0)),
+			Opcode::Halt => Op::Halt,
+		};
+
+		Ok(op)
+	}
+
+	fn arg(&self, operand: Operand) -> Result<i64, VmError> {
+		let pos = self.computer.pos;
+
+		match operand {
+			Operand::Position(n) => Ok(self.get(address(n, pos)?)),
+			Operand::Immediate(n) => Ok(n),
+			Operand::Relative(n) => Ok(self.get(self.relative(n)?)),
+		}
+	}
+
+	fn relative(&self, n: i64) -> Result<usize, VmError> {
+		let pos = self.computer.pos;
+		let target = n
+			.checked_add(self.computer.base)
+			.ok_or(VmError::Overflow { addr: pos })?;
+
+		address(target, pos)
+	}
+
+	fn target(&self, operand: Operand) -> Result<usize, VmError> {
+		let pos = self.computer.pos;
+
+		match operand {
+			Operand::Position(n) => address(n, pos),
+			Operand::Immediate(_) => Err(VmError::ImmediateWrite { addr: pos }),
+			Operand::Relative(n) => self.relative(n),
+		}
+	}
+
+	fn op<F>(
+		&mut self,
+		x: Operand,
+		y: Operand,
+		z: Operand,
+		f: F,
+	) -> Result<(), VmError>
+	where
+		F: Fn(i64, i64) -> Option<i64>,
+	{
+		let pos = self.computer.pos;
+		let x = self.arg(x)?;
+		let y = self.arg(y)?;
+		let j = self.target(z)?;
+		let value = f(x, y).ok_or(VmError::Overflow { addr: pos })?;
+
+		self.set(j, value);
+		self.computer.pos += 4;
+
+		Ok(())
+	}
+
+	fn jump(
+		&mut self,
+		x: Operand,
+		y: Operand,
+		mode: bool,
+	) -> Result<(), VmError> {
+		let x = self.arg(x)?;
+		let y = self.arg(y)?;
+
+		if (x != 0) == mode {
+			self.computer.pos = address(y, self.computer.pos)?;
+		} else {
+			self.computer.pos += 3;
+		}
+
+		Ok(())
+	}
+}
+
+#[test]
+fn test_self_modifying() {
+	// Outputs an immediate three times, incrementing it in place in between
+	let mut cached = Cached::from(Computer::parse(
+		"1001,18,1,18,104,5,1001,5,1,5,1007,18,3,19,1005,19,0,99,0,0",
+	));
+	let mut io = Buffer::default();
+
+	assert_eq!(cached.run(&mut io), Ok(Halt::Exit));
+	assert_eq!(io.outputs, vec![5, 6, 7]);
+}
+
+#[test]
+fn test_matches_computer() {
+	let input = include_str!("../../calendar/day-09/input.txt");
+
+	for program in [
+		"109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+		"3,0,4,0,3,0,4,0,99",
+		"1,0,0,0,42",
+		"10001,0,0,0",
+		"1105,1,-3",
+		"1102,9223372036854775807,2,0",
+		"109,9223372036854775807,2201,1,0,0",
+		input,
+	] {
+		let mut computer = Computer::parse(program);
+		let mut cached = Cached::from(computer.clone());
+		let mut expected = Buffer::new(vec![1]);
+		let mut io = Buffer::new(vec![1]);
+
+		assert_eq!(cached.run(&mut io), computer.run(&mut expected));
+		assert_eq!(io.outputs, expected.outputs);
+		assert_eq!(cached.computer().memory(), computer.memory());
+	}
+}
+
+#[test]
+fn test_far_code() {
+	// Writes a halt far past the end of the program and jumps to it
+	let far = 100_000_000_000i64;
+	let mut computer =
+		Computer::parse(&format!("1101,99,0,{},1105,1,{}", far, far));
+	let mut cached = Cached::from(computer.clone());
+	let mut expected = Buffer::default();
+	let mut io = Buffer::default();
+
+	assert_eq!(computer.run(&mut expected), Ok(Halt::Exit));
+	assert_eq!(cached.run(&mut io), Ok(Halt::Exit));
+	assert_eq!(cached.computer().cells(), computer.cells());
+	assert!(cached.ops.len() < 10);
+
+	let cached = Cached::from(computer);
+
+	assert!(cached.ops.len() < 10);
+}
@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::disasm::{reachable, Line};
+use crate::instruction::{Instruction, Mode, Opcode};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+	// A jump that is taken
+	Jump(usize),
+	// Running on into the next block, including past a jump not taken
+	Fall(usize),
+	// A jump to this negative address, which stops the machine with a
+	// `NegativeAddress` error when taken
+	Fault(i64),
+}
+
+// Where a jump goes when it is taken
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Target {
+	Direct(usize),
+	Negative(i64),
+	// Only known when it runs
+	Indirect,
+}
+
+// A run of instructions only ever entered at the top and left at the bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+	pub start: usize,
+	pub end: usize,
+	pub edges: Vec<Edge>,
+	// Ends in a jump to an address only known when it runs
+	pub indirect: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cfg {
+	pub blocks: BTreeMap<usize, Block>,
+}
+
+// What the jump at `addr` may do: go to its target, fall through, or both
+fn branches(
+	nums: &[i64],
+	addr: usize,
+	instruction: Instruction,
+) -> (Option<Target>, bool) {
+	let cond = nums[addr + 1];
+	let when = instruction.opcode == Opcode::JumpTrue;
+	let target = match (instruction.modes[1], nums[addr + 2]) {
+		(Mode::Immediate, n) if n >= 0 => Target::Direct(n as usize),
+		(Mode::Immediate, n) => Target::Negative(n),
+		_ => Target::Indirect,
+	};
+
+	match instruction.modes[0] {
+		Mode::Immediate if (cond != 0) == when => (Some(target), false),
+		Mode::Immediate => (None, true),
+		_ => (Some(target), true),
+	}
+}
+
+impl Cfg {
+	// Splits the code reachable from address 0 into basic blocks. A block
+	// starts at 0, at the target of any direct jump, and after any jump.
+	pub fn new(nums: &[i64]) -> Self {
+		let code = reachable(nums);
+		let mut leaders = BTreeSet::new();
+
+		leaders.insert(0);
+
+		for (addr, instruction) in code.iter() {
+			let next = addr + instruction.size();
+
+			match instruction.opcode {
+				Opcode::JumpTrue | Opcode::JumpFalse => {
+					if let (Some(Target::Direct(target)), _) =
+						branches(nums, *addr, *instruction)
+					{
+						leaders.insert(target);
+					}
+
+					leaders.insert(next);
+				}
+				Opcode::Halt => {
+					leaders.insert(next);
+				}
+				_ => {}
+			}
+		}
+
+		let mut blocks = BTreeMap::new();
+		let mut addrs = code.iter().peekable();
+
+		while let Some((start, instruction)) = addrs.next() {
+			let mut last = (*start, *instruction);
+
+			while let Some((addr, instruction)) = addrs.peek() {
+				let end = last.0 + last.1.size();
+
+				if **addr != end || leaders.contains(*addr) {
+					break;
+				}
+
+				last = (**addr, **instruction);
+				addrs.next();
+			}
+
+			let (addr, instruction) = last;
+			let end = addr + instruction.size();
+			let fall = |edges: &mut Vec<Edge>| {
+				if code.contains_key(&end) {
+					edges.push(Edge::Fall(end));
+				}
+			};
+
+			let mut edges = Vec::new();
+			let mut indirect = false;
+
+			match instruction.opcode {
+				Opcode::Halt => {}
+				Opcode::JumpTrue | Opcode::JumpFalse => {
+					let (jump, falls) = branches(nums, addr, instruction);
+
+					match jump {
+						Some(Target::Direct(target))
+							if code.contains_key(&target) =>
+						{
+							edges.push(Edge::Jump(target))
+						}
+						Some(Target::Negative(target)) => {
+							edges.push(Edge::Fault(target))
+						}
+						Some(Target::Indirect) => indirect = true,
+						_ => {}
+					}
+
+					if falls {
+						fall(&mut edges);
+					}
+				}
+				_ => fall(&mut edges),
+			}
+
+			blocks.insert(
+				*start,
+				Block {
+					start: *start,
+					end,
+					edges,
+					indirect,
+				},
+			);
+		}
+
+		Cfg { blocks }
+	}
+
+	// A Graphviz digraph with each block's disassembly. Jumps are solid
+	// edges and fall through dashed. Blocks ending in an indirect jump are
+	// drawn in red, as their successors are unknown, and a jump to a negative
+	// address goes to a fault node of its own.
+	pub fn dot(&self, nums: &[i64]) -> String {
+		let mut out = String::from(
+			"digraph cfg {\n\tnode [shape=box fontname=monospace];\n",
+		);
+
+		for block in self.blocks.values() {
+			let mut label = String::new();
+			let mut addr = block.start;
+
+			while addr < block.end {
+				let line =
+					Line::decode(|i| nums.get(i).cloned().unwrap_or(0), addr);
+
+				let _ = write!(label, "{:>6}: {}\\l", addr, line);
+				addr += line.size();
+			}
+
+			let style = match block.indirect {
+				true => " color=red",
+				false => "",
+			};
+
+			let _ = writeln!(
+				out,
+				"\tb{} [label=\"{}\"{}];",
+				block.start, label, style
+			);
+
+			for edge in block.edges.iter() {
+				let _ = match edge {
+					Edge::Jump(to) => {
+						writeln!(out, "\tb{} -> b{};", block.start, to)
+					}
+					Edge::Fall(to) => {
+						writeln!(
+							out,
+							"\tb{} -> b{} [style=dashed];",
+							block.start, to
+						)
+					}
+					Edge::Fault(to) => {
+						writeln!(
+							out,
+							"\tf{0} [label=\"fault {1}\" shape=octagon color=red];\n\tb{0} -> f{0};",
+							block.start, to
+						)
+					}
+				};
+			}
+		}
+
+		out + "}\n"
+	}
+}
+
+#[test]
+fn test_blocks() {
+	// Counts [15] down to zero, then jumps to 10, which outputs it and returns
+	// through [16]
+	let nums = vec![
+		1001, 15, -1, 15, 1005, 15, 0, 1105, 1, 10, 4, 15, 106, 0, 16, 3, 0,
+	];
+	let cfg = Cfg::new(&nums);
+	let starts = cfg.blocks.keys().cloned().collect::<Vec<_>>();
+
+	assert_eq!(starts, vec![0, 7, 10]);
+	assert_eq!(cfg.blocks[&0].edges, vec![Edge::Jump(0), Edge::Fall(7)]);
+	assert_eq!(cfg.blocks[&7].edges, vec![Edge::Jump(10)]);
+	assert_eq!(cfg.blocks[&10].end, 15);
+	assert!(cfg.blocks[&10].indirect);
+	assert!(cfg.blocks[&10].edges.is_empty());
+
+	let dot = cfg.dot(&nums);
+
+	assert!(dot.contains("\tb0 -> b7 [style=dashed];\n"));
+	assert!(dot.contains(
+		"\tb10 [label=\"    10: OUT [15]\\l    12: JF #0, [16]\\l\" color=red];\n"
+	));
+}
+
+#[test]
+fn test_negative_jump() {
+	// Always jumps to -1. The halt after it is still code, as with any
+	// direct jump, but there is no edge to it.
+	let nums = vec![1105, 1, -1, 99];
+	let cfg = Cfg::new(&nums);
+
+	assert_eq!(cfg.blocks.keys().cloned().collect::<Vec<_>>(), vec![0, 3]);
+	assert_eq!(cfg.blocks[&0].edges, vec![Edge::Fault(-1)]);
+	assert!(!cfg.blocks[&0].indirect);
+
+	let dot = cfg.dot(&nums);
+
+	assert!(
+		dot.contains("\tf0 [label=\"fault -1\" shape=octagon color=red];\n")
+	);
+	assert!(dot.contains("\tb0 -> f0;\n"));
+}
@@ -0,0 +1,68 @@
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::io::Io;
+
+#[cfg(test)]
+use crate::computer::{Computer, Halt};
+#[cfg(test)]
+use crate::error::VmError;
+#[cfg(test)]
+use std::sync::mpsc::channel;
+
+// Connects a machine to others through channels, so that each one can run on
+// its own thread and block until its input arrives. Once every sender of the
+// input is gone the machine starves, and outputs nobody is listening to any
+// more are dropped.
+#[derive(Debug)]
+pub struct Channel {
+	input: Receiver<i64>,
+	output: Sender<i64>,
+}
+
+impl Channel {
+	pub fn new(input: Receiver<i64>, output: Sender<i64>) -> Self {
+		Channel { input, output }
+	}
+}
+
+impl Io for Channel {
+	fn input(&mut self) -> Option<i64> {
+		self.input.recv().ok()
+	}
+
+	fn output(&mut self, n: i64) {
+		let _ = self.output.send(n);
+	}
+}
+
+#[test]
+fn test_channel() {
+	// Adds one to each input until it reads a zero
+	let program = "3,15,1005,15,6,99,1001,15,1,16,4,16,1105,1,0,0,0";
+	let computer = &Computer::parse(program);
+
+	let (tx, a) = channel();
+	let (b, c) = channel();
+	let (d, rx) = channel();
+
+	std::thread::scope(|scope| {
+		let mut first = Channel::new(a, b);
+		let mut second = Channel::new(c, d);
+		let first = scope.spawn(move || computer.clone().run(&mut first));
+		let second = scope.spawn(move || computer.clone().run(&mut second));
+
+		for n in [1, 2, 3] {
+			tx.send(n).unwrap();
+
+			assert_eq!(rx.recv(), Ok(n + 2));
+		}
+
+		tx.send(0).unwrap();
+
+		assert_eq!(first.join().unwrap(), Ok(Halt::Exit));
+		assert_eq!(
+			second.join().unwrap(),
+			Err(VmError::InputStarved { addr: 0 })
+		);
+	});
+}
@@ -0,0 +1,375 @@
+use crate::error::VmError;
+use crate::instruction::{Instruction, Mode, Opcode};
+use crate::io::Io;
+use crate::memory::{Dense, Memory};
+
+#[cfg(test)]
+use crate::io::Buffer;
+#[cfg(test)]
+use crate::memory::Sparse;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Halt {
+	Exit,
+}
+
+#[derive(Clone, Debug)]
+pub struct Computer<M: Memory = Dense> {
+	pub base: i64,
+	pub pos: usize,
+	memory: M,
+}
+
+impl Computer {
+	pub fn parse(input: &str) -> Self {
+		Computer::new(crate::parse(input))
+	}
+
+	pub fn new(nums: Vec<i64>) -> Self {
+		Computer::with_memory(Dense::from(nums))
+	}
+}
+
+impl<M: Memory> Computer<M> {
+	pub fn with_memory(memory: M) -> Self {
+		Computer {
+			base: 0,
+			pos: 0,
+			memory,
+		}
+	}
+
+	pub fn run<T: Io>(&mut self, io: &mut T) -> Result<Halt, VmError> {
+		loop {
+			if let Some(halt) = self.step(io)? {
+				return Ok(halt);
+			}
+		}
+	}
+
+	pub fn step<T: Io>(&mut self, io: &mut T) -> Result<Option<Halt>, VmError> {
+		let instruction = Instruction::decode(self.get(self.pos), self.pos)?;
+
+		match instruction.opcode {
+			Opcode::Add => self.add(instruction)?,
+			Opcode::Multiply => self.multiply(instruction)?,
+			Opcode::Input => self.input(instruction, io)?,
+			Opcode::Output => self.output(instruction, io)?,
+			Opcode::JumpTrue => self.jump(instruction, true)?,
+			Opcode::JumpFalse => self.jump(instruction, false)?,
+			Opcode::LessThan => self.compare(instruction, true)?,
+			Opcode::Equals => self.compare(instruction, false)?,
+			Opcode::IncrementBase => self.increment_base(instruction)?,
+			Opcode::Halt => return Ok(Some(Halt::Exit)),
+		}
+
+		Ok(None)
+	}
+
+	pub fn get(&self, i: usize) -> i64 {
+		self.memory.get(i)
+	}
+
+	pub fn set(&mut self, i: usize, n: i64) {
+		self.memory.set(i, n);
+	}
+
+	pub fn cells(&self) -> Vec<(usize, i64)> {
+		self.memory.cells()
+	}
+
+	pub fn memory(&self) -> Vec<i64> {
+		let len = self.cells().last().map(|(i, _)| i + 1).unwrap_or(0);
+
+		(0..len).map(|i| self.get(i)).collect()
+	}
+
+	pub(crate) fn index(
+		&self,
+		instruction: Instruction,
+		i: usize,
+	) -> Result<usize, VmError> {
+		let j = self.pos + i + 1;
+		let target = match instruction.modes[i] {
+			Mode::Position => self.get(j),
+			Mode::Immediate => return Ok(j),
+			Mode::Relative => self
+				.get(j)
+				.checked_add(self.base)
+				.ok_or(VmError::Overflow { addr: self.pos })?,
+		};
+
+		address(target, self.pos)
+	}
+
+	// The cell the instruction at `pos` writes to, if it writes to one
+	pub fn write_target(&self) -> Option<usize> {
+		let instruction =
+			Instruction::decode(self.get(self.pos), self.pos).ok()?;
+		let target = instruction.opcode.target()?;
+
+		self.operands(instruction)
+			.into_iter()
+			.find(|(i, _)| *i == target)
+			.map(|(_, j)| j)
+	}
+
+	// The cells an instruction reads, and for its target writes, as
+	// (parameter, address) pairs. Worked out before the step, which may
+	// overwrite the parameters. One that cannot be resolved is left out, as
+	// the step itself fails on it.
+	pub(crate) fn operands(
+		&self,
+		instruction: Instruction,
+	) -> Vec<(usize, usize)> {
+		(0..instruction.opcode.arity())
+			.filter(|i| instruction.modes[*i] != Mode::Immediate)
+			.filter_map(|i| Some((i, self.index(instruction, i).ok()?)))
+			.collect()
+	}
+
+	fn target(
+		&self,
+		instruction: Instruction,
+		i: usize,
+	) -> Result<usize, VmError> {
+		if instruction.modes[i] == Mode::Immediate {
+			return Err(VmError::ImmediateWrite { addr: self.pos });
+		}
+
+		self.index(instruction, i)
+	}
+
+	fn arg(&self, instruction: Instruction, i: usize) -> Result<i64, VmError> {
+		let j = self.index(instruction, i)?;
+
+		Ok(self.get(j))
+	}
+
+	fn op<F>(&mut self, instruction: Instruction, f: F) -> Result<(), VmError>
+	where
+		F: Fn(i64, i64) -> Option<i64>,
+	{
+		let x = self.arg(instruction, 0)?;
+		let y = self.arg(instruction, 1)?;
+		let j = self.target(instruction, 2)?;
+		let value = f(x, y).ok_or(VmError::Overflow { addr: self.pos })?;
+
+		self.set(j, value);
+		self.pos += 4;
+
+		Ok(())
+	}
+
+	fn add(&mut self, instruction: Instruction) -> Result<(), VmError> {
+		self.op(instruction, i64::checked_add)
+	}
+
+	fn multiply(&mut self, instruction: Instruction) -> Result<(), VmError> {
+		self.op(instruction, i64::checked_mul)
+	}
+
+	fn input<T: Io>(
+		&mut self,
+		instruction: Instruction,
+		io: &mut T,
+	) -> Result<(), VmError> {
+		let j = self.target(instruction, 0)?;
+		let input =
+			io.input().ok_or(VmError::InputStarved { addr: self.pos })?;
+
+		self.set(j, input);
+		self.pos += 2;
+
+		Ok(())
+	}
+
+	fn output<T: Io>(
+		&mut self,
+		instruction: Instruction,
+		io: &mut T,
+	) -> Result<(), VmError> {
+		let output = self.arg(instruction, 0)?;
+
+		io.output(output);
+		self.pos += 2;
+
+		Ok(())
+	}
+
+	fn increment_base(
+		&mut self,
+		instruction: Instruction,
+	) -> Result<(), VmError> {
+		let n = self.arg(instruction, 0)?;
+
+		self.base = self
+			.base
+			.checked_add(n)
+			.ok_or(VmError::Overflow { addr: self.pos })?;
+		self.pos += 2;
+
+		Ok(())
+	}
+
+	fn jump(
+		&mut self,
+		instruction: Instruction,
+		mode: bool,
+	) -> Result<(), VmError> {
+		let x = self.arg(instruction, 0)?;
+		let y = self.arg(instruction, 1)?;
+
+		if (x != 0 && mode) || (x == 0 && !mode) {
+			self.pos = address(y, self.pos)?;
+		} else {
+			self.pos += 3;
+		}
+
+		Ok(())
+	}
+
+	fn compare(
+		&mut self,
+		instruction: Instruction,
+		mode: bool,
+	) -> Result<(), VmError> {
+		let x = self.arg(instruction, 0)?;
+		let y = self.arg(instruction, 1)?;
+		let j = self.target(instruction, 2)?;
+
+		let value = if (mode && x < y) || (!mode && x == y) {
+			1
+		} else {
+			0
+		};
+
+		self.set(j, value);
+		self.pos += 4;
+
+		Ok(())
+	}
+}
+
+pub(crate) fn address(target: i64, addr: usize) -> Result<usize, VmError> {
+	if target < 0 {
+		return Err(VmError::NegativeAddress { addr, target });
+	}
+
+	Ok(target as usize)
+}
+
+#[test]
+fn test_run() {
+	let opcodes = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+	let mut computer = Computer::new(opcodes.clone());
+	let mut sparse = Computer::with_memory(Sparse::from(opcodes));
+	let expected = vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50];
+
+	assert_eq!(computer.run(&mut Buffer::default()), Ok(Halt::Exit));
+	assert_eq!(computer.memory(), expected);
+	assert_eq!(sparse.run(&mut Buffer::default()), Ok(Halt::Exit));
+	assert_eq!(sparse.memory(), expected);
+}
+
+#[test]
+fn test_compare() {
+	let computer = Computer::parse("3,9,8,9,10,9,4,9,99,-1,8");
+
+	for (input, expected) in [(8, 1), (7, 0)] {
+		let mut io = Buffer::new(vec![input]);
+
+		computer.clone().run(&mut io).unwrap();
+
+		assert_eq!(io.outputs, vec![expected]);
+	}
+}
+
+#[test]
+fn test_starved() {
+	let mut computer = Computer::parse("3,0,4,0,3,0,4,0,99");
+	let mut io = Buffer::new(vec![1]);
+
+	assert_eq!(
+		computer.run(&mut io),
+		Err(VmError::InputStarved { addr: 4 })
+	);
+	assert_eq!(io.drain(), vec![1]);
+
+	io.push(2);
+
+	assert_eq!(computer.run(&mut io), Ok(Halt::Exit));
+	assert_eq!(io.drain(), vec![2]);
+}
+
+#[test]
+fn test_errors() {
+	let run = |input: &str| Computer::parse(input).run(&mut Buffer::default());
+
+	assert_eq!(
+		run("1,0,0,0,42"),
+		Err(VmError::InvalidOpcode {
+			addr: 4,
+			opcode: 42
+		})
+	);
+	assert_eq!(
+		run("301,0,0,0"),
+		Err(VmError::InvalidMode { addr: 0, mode: 3 })
+	);
+	assert_eq!(
+		run("1,-1,0,0"),
+		Err(VmError::NegativeAddress {
+			addr: 0,
+			target: -1
+		})
+	);
+	assert_eq!(
+		run("109,-5,2201,0,0,0"),
+		Err(VmError::NegativeAddress {
+			addr: 2,
+			target: -5
+		})
+	);
+	assert_eq!(
+		run("1105,1,-3"),
+		Err(VmError::NegativeAddress {
+			addr: 0,
+			target: -3
+		})
+	);
+	assert_eq!(run("10001,0,0,0"), Err(VmError::ImmediateWrite { addr: 0 }));
+
+	let max = i64::MAX;
+
+	assert_eq!(
+		run(&format!("1,0,0,0,1101,{},1,0", max)),
+		Err(VmError::Overflow { addr: 4 })
+	);
+	assert_eq!(
+		run(&format!("1102,{},2,0", max)),
+		Err(VmError::Overflow { addr: 0 })
+	);
+	assert_eq!(
+		run(&format!("109,{},2201,1,0,0", max)),
+		Err(VmError::Overflow { addr: 2 })
+	);
+	assert_eq!(
+		run(&format!("109,{},109,1", max)),
+		Err(VmError::Overflow { addr: 2 })
+	);
+}
+
+#[test]
+fn test_quine() {
+	let nums = vec![
+		109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0,
+		99,
+	];
+
+	let mut io = Buffer::default();
+
+	Computer::new(nums.clone()).run(&mut io).unwrap();
+
+	assert_eq!(io.outputs, nums);
+}
@@ -0,0 +1,371 @@
+use std::io::{BufRead, Write};
+
+use crate::computer::{Computer, Halt};
+use crate::disasm::Line;
+use crate::error::VmError;
+use crate::history::History;
+use crate::instruction::Opcode;
+use crate::io::Io;
+use crate::memory::{Dense, Memory};
+
+#[cfg(test)]
+use crate::io::Buffer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Breakpoint {
+	Addr(usize),
+	Opcode(Opcode),
+}
+
+impl Breakpoint {
+	pub fn parse(s: &str) -> Option<Self> {
+		match s.parse::<usize>() {
+			Ok(addr) => Some(Breakpoint::Addr(addr)),
+			Err(_) => Opcode::from_mnemonic(s).map(Breakpoint::Opcode),
+		}
+	}
+}
+
+// How many instructions can be stepped back over
+const HISTORY: usize = 100_000;
+
+// `mem` prints this many cells per row, and at most `MEM_ROWS` rows
+const MEM_WIDTH: usize = 8;
+const MEM_ROWS: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+	Step,
+	Breakpoint(usize),
+	Halt(Halt),
+	Error(VmError),
+}
+
+pub struct Debugger<M: Memory = Dense> {
+	pub computer: Computer<M>,
+	breakpoints: Vec<Breakpoint>,
+	history: History,
+}
+
+impl<M: Memory> Debugger<M> {
+	pub fn new(computer: Computer<M>) -> Self {
+		Debugger {
+			computer,
+			breakpoints: Vec::new(),
+			history: History::with_limit(HISTORY),
+		}
+	}
+
+	pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+		if !self.breakpoints.contains(&breakpoint) {
+			self.breakpoints.push(breakpoint);
+		}
+	}
+
+	pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+		let len = self.breakpoints.len();
+
+		self.breakpoints.retain(|b| b != &breakpoint);
+		self.breakpoints.len() != len
+	}
+
+	pub fn breakpoints(&self) -> &[Breakpoint] {
+		&self.breakpoints
+	}
+
+	fn is_breakpoint(&self) -> bool {
+		let pos = self.computer.pos;
+		let opcode = Opcode::parse(self.computer.get(pos));
+
+		self.breakpoints.iter().any(|breakpoint| match breakpoint {
+			Breakpoint::Addr(addr) => *addr == pos,
+			Breakpoint::Opcode(op) => opcode == Some(*op),
+		})
+	}
+
+	// A single instruction, whether or not it sits on a breakpoint
+	pub fn step<T: Io>(&mut self, io: &mut T) -> Stop {
+		match self.computer.step_logged(io, &mut self.history) {
+			Ok(Some(halt)) => Stop::Halt(halt),
+			Ok(None) => Stop::Step,
+			Err(err) => Stop::Error(err),
+		}
+	}
+
+	// Always executes at least one instruction so that continuing from a
+	// breakpoint does not immediately stop on it again.
+	pub fn cont<T: Io>(&mut self, io: &mut T) -> Stop {
+		loop {
+			let stop = self.step(io);
+
+			if stop != Stop::Step {
+				return stop;
+			}
+
+			if self.is_breakpoint() {
+				return Stop::Breakpoint(self.computer.pos);
+			}
+		}
+	}
+
+	// Undoes the last instruction, returning false once there is nothing
+	// left to undo
+	pub fn back(&mut self) -> bool {
+		self.history.undo(&mut self.computer).is_some()
+	}
+
+	pub fn current(&self) -> Line {
+		Line::decode(|i| self.computer.get(i), self.computer.pos)
+	}
+
+	pub fn list(&self, n: usize) -> Vec<Line> {
+		let mut addr = self.computer.pos;
+
+		(0..n)
+			.map(|_| {
+				let line = Line::decode(|i| self.computer.get(i), addr);
+
+				addr += line.size();
+				line
+			})
+			.collect()
+	}
+
+	pub fn repl<T, R, W>(
+		&mut self,
+		io: &mut T,
+		input: R,
+		mut output: W,
+	) -> std::io::Result<()>
+	where
+		T: Io,
+		R: BufRead,
+		W: Write,
+	{
+		writeln!(output, "{:>6}: {}", self.computer.pos, self.current())?;
+
+		for line in input.lines() {
+			let line = line?;
+			let args = line.split_whitespace().collect::<Vec<_>>();
+			let num =
+				|i: usize| args.get(i).and_then(|s| s.parse::<i64>().ok());
+
+			let stop = match args.first().cloned() {
+				None => continue,
+				Some("q") | Some("quit") => return Ok(()),
+				Some("s") | Some("step") => {
+					let n = num(1).unwrap_or(1);
+					let mut stop = Stop::Step;
+
+					// Like `continue`, stepping several at once stops early
+					// on reaching a breakpoint
+					for i in 0..n {
+						stop = self.step(io);
+
+						if stop != Stop::Step {
+							break;
+						}
+
+						if i + 1 < n && self.is_breakpoint() {
+							stop = Stop::Breakpoint(self.computer.pos);
+							break;
+						}
+					}
+
+					Some(stop)
+				}
+				Some("c") | Some("continue") => Some(self.cont(io)),
+				Some("bs") | Some("back") => {
+					let n = num(1).unwrap_or(1);
+
+					for _ in 0..n {
+						if !self.back() {
+							writeln!(output, "No history")?;
+							break;
+						}
+					}
+
+					Some(Stop::Step)
+				}
+				Some("b") | Some("break") => {
+					match args.get(1).and_then(|s| Breakpoint::parse(s)) {
+						Some(breakpoint) => {
+							self.add_breakpoint(breakpoint);
+							writeln!(output, "Breakpoint: {:?}", breakpoint)?;
+						}
+						None => writeln!(output, "Usage: break <addr|op>")?,
+					}
+
+					None
+				}
+				Some("d") | Some("delete") => {
+					match args.get(1).and_then(|s| Breakpoint::parse(s)) {
+						Some(breakpoint)
+							if self.remove_breakpoint(breakpoint) =>
+						{
+							writeln!(output, "Deleted: {:?}", breakpoint)?
+						}
+						_ => writeln!(output, "No such breakpoint")?,
+					}
+
+					None
+				}
+				Some("r") | Some("regs") => {
+					writeln!(
+						output,
+						"pos: {} base: {}",
+						self.computer.pos, self.computer.base
+					)?;
+
+					None
+				}
+				Some("x") | Some("mem") => match num(1) {
+					Some(start) if start >= 0 => {
+						let start = start as usize;
+						let len = num(2).unwrap_or(8).max(0) as usize;
+						let end =
+							start.saturating_add(len.min(MEM_WIDTH * MEM_ROWS));
+
+						for row in (start..end).step_by(MEM_WIDTH) {
+							let values = (row..end.min(row + MEM_WIDTH))
+								.map(|i| self.computer.get(i).to_string())
+								.collect::<Vec<_>>();
+
+							writeln!(
+								output,
+								"{:>6}: {}",
+								row,
+								values.join(", ")
+							)?;
+						}
+
+						None
+					}
+					_ => {
+						writeln!(output, "Usage: mem <addr> [len]")?;
+
+						None
+					}
+				},
+				Some("w") | Some("set") => match (num(1), num(2)) {
+					(Some(addr), Some(value)) if addr >= 0 => {
+						self.computer.set(addr as usize, value);
+
+						None
+					}
+					_ => {
+						writeln!(output, "Usage: set <addr> <value>")?;
+
+						None
+					}
+				},
+				Some("l") | Some("list") => {
+					for line in self.list(num(1).unwrap_or(8).max(0) as usize) {
+						writeln!(output, "{:>6}: {}", line.addr(), line)?;
+					}
+
+					None
+				}
+				Some(_) => {
+					writeln!(
+						output,
+						"Commands: step [n], back [n], continue, \
+						 break <addr|op>, delete <addr|op>, regs, mem <addr> [len], \
+						 set <addr> <value>, list [n], quit"
+					)?;
+
+					None
+				}
+			};
+
+			match stop {
+				Some(Stop::Halt(halt)) => {
+					writeln!(output, "Halted: {:?}", halt)?
+				}
+				Some(Stop::Error(err)) => writeln!(output, "Error: {}", err)?,
+				Some(Stop::Breakpoint(addr)) => {
+					writeln!(output, "Breakpoint at {}", addr)?;
+					writeln!(output, "{:>6}: {}", addr, self.current())?;
+				}
+				Some(Stop::Step) => {
+					writeln!(
+						output,
+						"{:>6}: {}",
+						self.computer.pos,
+						self.current()
+					)?;
+				}
+				None => {}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[test]
+fn test_breakpoints() {
+	let computer = Computer::parse("3,0,4,0,1001,0,1,0,4,0,99");
+	let mut debugger = Debugger::new(computer);
+	let mut io = Buffer::new(vec![5]);
+
+	debugger.add_breakpoint(Breakpoint::Addr(4));
+	debugger.add_breakpoint(Breakpoint::Opcode(Opcode::Output));
+
+	assert_eq!(debugger.cont(&mut io), Stop::Breakpoint(2));
+	assert_eq!(debugger.cont(&mut io), Stop::Breakpoint(4));
+	assert_eq!(io.outputs, vec![5]);
+
+	assert!(debugger.remove_breakpoint(Breakpoint::Opcode(Opcode::Output)));
+
+	debugger.computer.set(6, 10);
+
+	assert_eq!(debugger.cont(&mut io), Stop::Halt(Halt::Exit));
+	assert_eq!(io.outputs, vec![5, 15]);
+}
+
+#[test]
+fn test_repl() {
+	let computer = Computer::parse("1101,2,3,7,4,7,99,0");
+	let mut debugger = Debugger::new(computer);
+	let script = "step\nregs\nmem 7 1\nset 7 42\nbreak HLT\ncontinue\nset 6 42\nstep\nback\nquit\n";
+	let mut output = Vec::new();
+
+	debugger
+		.repl(&mut Buffer::default(), script.as_bytes(), &mut output)
+		.unwrap();
+
+	let output = String::from_utf8(output).unwrap();
+	let expected = "     0: ADD #2, #3 -> [7]
+     4: OUT [7]
+pos: 4 base: 0
+     7: 5
+Breakpoint: Opcode(Halt)
+Breakpoint at 6
+     6: HLT
+Error: Unexpected opcode 42 at 6
+     4: OUT [7]
+";
+
+	assert_eq!(output, expected);
+}
+
+#[test]
+fn test_repl_limits() {
+	let computer = Computer::parse("1101,2,3,7,4,7,99,0");
+	let mut debugger = Debugger::new(computer);
+	let script = "break 4\nstep 5\nmem 0 10\nmem 1 99999999999999\n";
+	let mut output = Vec::new();
+
+	debugger
+		.repl(&mut Buffer::default(), script.as_bytes(), &mut output)
+		.unwrap();
+
+	let output = String::from_utf8(output).unwrap();
+	let lines = output.lines().collect::<Vec<_>>();
+
+	assert_eq!(lines[2], "Breakpoint at 4");
+	assert_eq!(lines[4], "     0: 1101, 2, 3, 7, 4, 7, 99, 5");
+	assert_eq!(lines[5], "     8: 0, 0");
+	assert_eq!(lines.len(), 6 + MEM_ROWS);
+}
@@ -0,0 +1,108 @@
+use crate::io::Io;
+
+#[cfg(test)]
+use crate::computer::{Computer, Halt};
+#[cfg(test)]
+use crate::error::VmError;
+
+// A peripheral on a machine: it receives everything the machine outputs and
+// supplies its input. Every device can drive a machine on its own, or share
+// one with others on a `Bus`.
+pub trait Device {
+	fn receive(&mut self, n: i64);
+	// `None` when the device has nothing to send
+	fn send(&mut self) -> Option<i64>;
+}
+
+impl<D: Device> Io for D {
+	fn input(&mut self) -> Option<i64> {
+		self.send()
+	}
+
+	fn output(&mut self, n: i64) {
+		self.receive(n);
+	}
+}
+
+// Several devices on one machine. Every output goes to all of them, and each
+// input comes from the first, in the order attached, that has one to send. A
+// bus is a device itself, so buses can be nested.
+#[derive(Default)]
+pub struct Bus<'a> {
+	devices: Vec<&'a mut dyn Device>,
+}
+
+impl<'a> Bus<'a> {
+	pub fn new() -> Self {
+		Bus::default()
+	}
+
+	pub fn attach(&mut self, device: &'a mut dyn Device) -> &mut Self {
+		self.devices.push(device);
+		self
+	}
+}
+
+impl Device for Bus<'_> {
+	fn receive(&mut self, n: i64) {
+		for device in self.devices.iter_mut() {
+			device.receive(n);
+		}
+	}
+
+	fn send(&mut self) -> Option<i64> {
+		self.devices.iter_mut().find_map(|device| device.send())
+	}
+}
+
+#[cfg(test)]
+struct Keypad {
+	keys: Vec<i64>,
+	seen: Vec<i64>,
+}
+
+#[cfg(test)]
+impl Device for Keypad {
+	fn receive(&mut self, n: i64) {
+		self.seen.push(n);
+	}
+
+	fn send(&mut self) -> Option<i64> {
+		self.keys.pop()
+	}
+}
+
+#[test]
+fn test_bus() {
+	// Adds two inputs together, twice
+	let program = "3,13,3,14,1,13,14,15,4,15,1105,1,0,0,0,0";
+	let mut first = Keypad {
+		keys: vec![2, 1],
+		seen: Vec::new(),
+	};
+	let mut second = Keypad {
+		keys: vec![20, 10],
+		seen: Vec::new(),
+	};
+	let mut bus = Bus::new();
+
+	bus.attach(&mut first).attach(&mut second);
+
+	assert_eq!(
+		Computer::parse(program).run(&mut bus),
+		Err(VmError::InputStarved { addr: 0 })
+	);
+	assert_eq!(first.seen, vec![3, 30]);
+	assert_eq!(second.seen, vec![3, 30]);
+
+	let mut keypad = Keypad {
+		keys: vec![99],
+		seen: Vec::new(),
+	};
+
+	assert_eq!(
+		Computer::parse("3,3,104,0,99").run(&mut keypad),
+		Ok(Halt::Exit)
+	);
+	assert_eq!(keypad.seen, vec![99]);
+}
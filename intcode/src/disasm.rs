@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::instruction::{Instruction, Mode, Opcode};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Param {
+	pub mode: Mode,
+	pub value: i64,
+}
+
+impl fmt::Display for Param {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.mode {
+			Mode::Position => write!(f, "[{}]", self.value),
+			Mode::Immediate => write!(f, "#{}", self.value),
+			Mode::Relative if self.value < 0 => write!(f, "rb{}", self.value),
+			Mode::Relative => write!(f, "rb+{}", self.value),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Line {
+	Code {
+		addr: usize,
+		opcode: Opcode,
+		params: Vec<Param>,
+	},
+	Data {
+		addr: usize,
+		values: Vec<i64>,
+	},
+}
+
+impl Line {
+	pub fn decode<F>(get: F, addr: usize) -> Self
+	where
+		F: Fn(usize) -> i64,
+	{
+		match Instruction::parse(get(addr)) {
+			Some(instruction) => Line::Code {
+				addr,
+				opcode: instruction.opcode,
+				params: (0..instruction.opcode.arity())
+					.map(|i| Param {
+						mode: instruction.modes[i],
+						value: get(addr + i + 1),
+					})
+					.collect(),
+			},
+			None => Line::Data {
+				addr,
+				values: vec![get(addr)],
+			},
+		}
+	}
+
+	pub fn size(&self) -> usize {
+		match self {
+			Line::Code { params, .. } => params.len() + 1,
+			Line::Data { values, .. } => values.len(),
+		}
+	}
+
+	pub fn addr(&self) -> usize {
+		match self {
+			Line::Code { addr, .. } | Line::Data { addr, .. } => *addr,
+		}
+	}
+}
+
+impl fmt::Display for Line {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Line::Code { opcode, params, .. } => {
+				write!(f, "{}", opcode.mnemonic())?;
+
+				for (i, param) in params.iter().enumerate() {
+					let sep = match i {
+						_ if opcode.target() == Some(i) => " -> ",
+						0 => " ",
+						_ => ", ",
+					};
+
+					write!(f, "{}{}", sep, param)?;
+				}
+
+				Ok(())
+			}
+			Line::Data { values, .. } => {
+				let values = values
+					.iter()
+					.map(|n| n.to_string())
+					.collect::<Vec<_>>()
+					.join(", ");
+
+				write!(f, ".data {}", values)
+			}
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Listing(pub Vec<Line>);
+
+impl fmt::Display for Listing {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for line in self.0.iter() {
+			writeln!(f, "{:>6}: {}", line.addr(), line)?;
+		}
+
+		Ok(())
+	}
+}
+
+// Walks the program from address 0, following fall through and immediate
+// jump targets. Anything never reached is treated as data. Fall through
+// after an unconditional indirect jump (a `return`) is not followed, but it
+// is after a direct one since that is usually where a call returns to.
+pub(crate) fn reachable(nums: &[i64]) -> BTreeMap<usize, Instruction> {
+	let mut code = BTreeMap::new();
+	let mut pending = vec![0];
+
+	while let Some(addr) = pending.pop() {
+		if addr >= nums.len() || code.contains_key(&addr) {
+			continue;
+		}
+
+		let instruction = match Instruction::parse(nums[addr]) {
+			Some(instruction) => instruction,
+			None => continue,
+		};
+
+		if addr + instruction.size() > nums.len() {
+			continue;
+		}
+
+		code.insert(addr, instruction);
+
+		let next = addr + instruction.size();
+
+		match instruction.opcode {
+			Opcode::Halt => {}
+			Opcode::JumpTrue | Opcode::JumpFalse => {
+				let cond = nums[addr + 1];
+				let always = instruction.modes[0] == Mode::Immediate
+					&& ((cond != 0)
+						== (instruction.opcode == Opcode::JumpTrue));
+
+				if instruction.modes[1] == Mode::Immediate {
+					if nums[addr + 2] >= 0 {
+						pending.push(nums[addr + 2] as usize);
+					}
+
+					pending.push(next);
+				} else if !always {
+					pending.push(next);
+				}
+			}
+			_ => pending.push(next),
+		}
+	}
+
+	code
+}
+
+pub fn disassemble(nums: &[i64]) -> Listing {
+	let code = reachable(nums);
+	let mut lines = Vec::new();
+	let mut addr = 0;
+
+	while addr < nums.len() {
+		// Words the VM runs but that carry stray mode digits would not
+		// assemble back to themselves, so keep them as raw data
+		let canonical =
+			|instruction: &Instruction| instruction.encode() == nums[addr];
+
+		if code.get(&addr).is_some_and(canonical) {
+			let line = Line::decode(|i| nums[i], addr);
+
+			addr += line.size();
+			lines.push(line);
+		} else {
+			let end = code
+				.range((addr + 1)..)
+				.next()
+				.map(|(i, _)| *i)
+				.unwrap_or_else(|| nums.len());
+
+			for (i, values) in nums[addr..end].chunks(8).enumerate() {
+				lines.push(Line::Data {
+					addr: addr + i * 8,
+					values: values.to_vec(),
+				});
+			}
+
+			addr = end;
+		}
+	}
+
+	Listing(lines)
+}
+
+#[test]
+fn test_disassemble() {
+	let nums = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+	let listing = disassemble(&nums).to_string();
+	let expected = "     0: ADD [9], [10] -> [3]
+     4: MUL [3], [11] -> [0]
+     8: HLT
+     9: .data 30, 40, 50
+";
+
+	assert_eq!(listing, expected);
+}
+
+#[test]
+fn test_disassemble_modes() {
+	let nums = vec![21101, 12, 5, 3, 3, 7, 204, -1, 109, 4, 99];
+	let listing = disassemble(&nums).to_string();
+	let expected = "     0: ADD #12, #5 -> rb+3
+     4: IN -> [7]
+     6: OUT rb-1
+     8: ARB #4
+    10: HLT
+";
+
+	assert_eq!(listing, expected);
+}
+
+#[test]
+fn test_disassemble_jumps() {
+	let nums = vec![1105, 1, 7, 0, 0, 0, 0, 2106, 0, 0, 42];
+	let listing = disassemble(&nums).to_string();
+	let expected = "     0: JT #1, #7
+     3: .data 0, 0, 0, 0
+     7: JF #0, rb+0
+    10: .data 42
+";
+
+	assert_eq!(listing, expected);
+}
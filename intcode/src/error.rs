@@ -0,0 +1,51 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VmError {
+	InvalidOpcode { addr: usize, opcode: i64 },
+	InvalidMode { addr: usize, mode: i64 },
+	NegativeAddress { addr: usize, target: i64 },
+	ImmediateWrite { addr: usize },
+	InputStarved { addr: usize },
+	Overflow { addr: usize },
+}
+
+impl VmError {
+	pub fn addr(&self) -> usize {
+		match self {
+			VmError::InvalidOpcode { addr, .. }
+			| VmError::InvalidMode { addr, .. }
+			| VmError::NegativeAddress { addr, .. }
+			| VmError::ImmediateWrite { addr }
+			| VmError::InputStarved { addr }
+			| VmError::Overflow { addr } => *addr,
+		}
+	}
+}
+
+impl fmt::Display for VmError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			VmError::InvalidOpcode { addr, opcode } => {
+				write!(f, "Unexpected opcode {} at {}", opcode, addr)
+			}
+			VmError::InvalidMode { addr, mode } => {
+				write!(f, "Unexpected mode {} at {}", mode, addr)
+			}
+			VmError::NegativeAddress { addr, target } => {
+				write!(f, "Negative address {} at {}", target, addr)
+			}
+			VmError::ImmediateWrite { addr } => {
+				write!(f, "Write to an immediate operand at {}", addr)
+			}
+			VmError::InputStarved { addr } => {
+				write!(f, "Input required at {}", addr)
+			}
+			VmError::Overflow { addr } => {
+				write!(f, "Arithmetic overflow at {}", addr)
+			}
+		}
+	}
+}
+
+impl std::error::Error for VmError {}
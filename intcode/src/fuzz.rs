@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+
+use crate::cache::Cached;
+use crate::computer::{Computer, Halt};
+use crate::debugger::{Debugger, Stop};
+use crate::error::VmError;
+use crate::history::History;
+use crate::io::Buffer;
+use crate::memory::{Memory, Sparse};
+use crate::profile::Profile;
+
+// xorshift64*, which is plenty for making up programs
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+	fn new(seed: u64) -> Self {
+		Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+	}
+
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 >> 12;
+		self.0 ^= self.0 << 25;
+		self.0 ^= self.0 >> 27;
+		self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+	}
+
+	fn range(&mut self, lo: i64, hi: i64) -> i64 {
+		lo + (self.next() % (hi - lo) as u64) as i64
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum End {
+	Halt(Halt),
+	Error(VmError),
+	// Still running when the step limit ran out
+	Limit,
+}
+
+// Everything observable about a machine once it stops. Memory is every
+// non-zero cell, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+	pub pos: usize,
+	pub base: i64,
+	pub memory: Vec<(usize, i64)>,
+	pub outputs: Vec<i64>,
+	pub end: End,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+	pub program: Vec<i64>,
+	pub inputs: Vec<i64>,
+	pub engine: &'static str,
+	pub expected: State,
+	pub actual: State,
+}
+
+type Engine = fn(&[i64], &[i64], usize) -> State;
+
+const ENGINES: [(&str, Engine); 6] = [
+	("dense", dense),
+	("sparse", sparse),
+	("cached", cached),
+	("profiled", profiled),
+	("logged", logged),
+	("debugger", debugger),
+];
+
+// A program of roughly `len` cells of random instructions. Operands mostly
+// point back into the program, so it reads and rewrites its own code.
+pub fn generate(seed: u64, len: usize) -> (Vec<i64>, Vec<i64>) {
+	let mut rng = Rng::new(seed);
+	let mut program = Vec::new();
+	let size = len as i64 + 8;
+
+	while program.len() < len {
+		let opcode = match rng.range(0, 20) {
+			0..=2 => 1,
+			3..=5 => 2,
+			6 => 3,
+			7..=8 => 4,
+			9..=10 => 5,
+			11..=12 => 6,
+			13..=14 => 7,
+			15..=16 => 8,
+			17..=18 => 9,
+			_ => 99,
+		};
+		let arity = match opcode {
+			1 | 2 | 7 | 8 => 3,
+			5 | 6 => 2,
+			3 | 4 | 9 => 1,
+			_ => 0,
+		};
+		let target = match opcode {
+			1 | 2 | 7 | 8 => Some(2),
+			3 => Some(0),
+			_ => None,
+		};
+
+		let mut code = opcode;
+		let mut params = Vec::new();
+
+		for i in 0..arity {
+			let mode = match Some(i) == target {
+				true => rng.range(0, 2) * 2,
+				false => rng.range(0, 3),
+			};
+
+			let value = match (mode, opcode, i) {
+				(1, 5 | 6, 1) => rng.range(0, size),
+				(1, 9, _) => rng.range(-5, 6),
+				(1, ..) => rng.range(-10, 11),
+				(2, ..) => rng.range(-8, size),
+				_ => rng.range(0, size),
+			};
+
+			code += mode * 10i64.pow(i as u32 + 2);
+			params.push(value);
+		}
+
+		program.push(code);
+		program.extend(params);
+	}
+
+	let inputs = (0..rng.range(0, 8)).map(|_| rng.range(-10, 11)).collect();
+
+	(program, inputs)
+}
+
+// A plain interpreter written straight from the puzzle text, to check the
+// rest against
+struct Reference {
+	memory: HashMap<usize, i64>,
+	pos: usize,
+	base: i64,
+	modes: [i64; 3],
+}
+
+impl Reference {
+	fn get(&self, i: usize) -> i64 {
+		self.memory.get(&i).cloned().unwrap_or(0)
+	}
+
+	fn address(&self, i: usize) -> Result<usize, VmError> {
+		let value = self.get(self.pos + i + 1);
+		let target = match self.modes[i] {
+			0 => value,
+			1 => return Ok(self.pos + i + 1),
+			_ => value
+				.checked_add(self.base)
+				.ok_or(VmError::Overflow { addr: self.pos })?,
+		};
+
+		match target < 0 {
+			true => Err(VmError::NegativeAddress {
+				addr: self.pos,
+				target,
+			}),
+			false => Ok(target as usize),
+		}
+	}
+
+	fn read(&self, i: usize) -> Result<i64, VmError> {
+		Ok(self.get(self.address(i)?))
+	}
+
+	fn write(&self, i: usize) -> Result<usize, VmError> {
+		match self.modes[i] {
+			1 => Err(VmError::ImmediateWrite { addr: self.pos }),
+			_ => self.address(i),
+		}
+	}
+
+	fn step(
+		&mut self,
+		inputs: &mut impl Iterator<Item = i64>,
+		outputs: &mut Vec<i64>,
+	) -> Result<Option<Halt>, VmError> {
+		let n = self.get(self.pos);
+		let opcode = n % 100;
+		let arity = match opcode {
+			1 | 2 | 7 | 8 => 3,
+			5 | 6 => 2,
+			3 | 4 | 9 => 1,
+			99 => 0,
+			_ => {
+				return Err(VmError::InvalidOpcode {
+					addr: self.pos,
+					opcode,
+				})
+			}
+		};
+
+		for i in 0..arity {
+			let mode = n / 10i64.pow(i as u32 + 2) % 10;
+
+			if mode > 2 {
+				return Err(VmError::InvalidMode {
+					addr: self.pos,
+					mode,
+				});
+			}
+
+			self.modes[i] = mode;
+		}
+
+		match opcode {
+			1 | 2 | 7 | 8 => {
+				let x = self.read(0)?;
+				let y = self.read(1)?;
+				let j = self.write(2)?;
+				let value = match opcode {
+					1 => x
+						.checked_add(y)
+						.ok_or(VmError::Overflow { addr: self.pos })?,
+					2 => x
+						.checked_mul(y)
+						.ok_or(VmError::Overflow { addr: self.pos })?,
+					7 => (x < y) as i64,
+					_ => (x == y) as i64,
+				};
+
+				self.memory.insert(j, value);
+				self.pos += 4;
+			}
+			3 => {
+				let j = self.write(0)?;
+				let n = inputs
+					.next()
+					.ok_or(VmError::InputStarved { addr: self.pos })?;
+
+				self.memory.insert(j, n);
+				self.pos += 2;
+			}
+			4 => {
+				outputs.push(self.read(0)?);
+				self.pos += 2;
+			}
+			5 | 6 => {
+				let x = self.read(0)?;
+				let y = self.read(1)?;
+
+				if (x != 0) != (opcode == 5) {
+					self.pos += 3;
+				} else if y < 0 {
+					return Err(VmError::NegativeAddress {
+						addr: self.pos,
+						target: y,
+					});
+				} else {
+					self.pos = y as usize;
+				}
+			}
+			9 => {
+				let x = self.read(0)?;
+
+				self.base = self
+					.base
+					.checked_add(x)
+					.ok_or(VmError::Overflow { addr: self.pos })?;
+				self.pos += 2;
+			}
+			_ => return Ok(Some(Halt::Exit)),
+		}
+
+		Ok(None)
+	}
+}
+
+// Runs a program on the reference interpreter
+pub fn reference(program: &[i64], inputs: &[i64], limit: usize) -> State {
+	let mut machine = Reference {
+		memory: program.iter().cloned().enumerate().collect(),
+		pos: 0,
+		base: 0,
+		modes: [0; 3],
+	};
+	let mut inputs = inputs.iter().cloned();
+	let mut outputs = Vec::new();
+	let mut end = End::Limit;
+
+	for _ in 0..limit {
+		match machine.step(&mut inputs, &mut outputs) {
+			Ok(None) => {}
+			Ok(Some(halt)) => {
+				end = End::Halt(halt);
+				break;
+			}
+			Err(err) => {
+				end = End::Error(err);
+				break;
+			}
+		}
+	}
+
+	let mut memory = machine
+		.memory
+		.into_iter()
+		.filter(|(_, n)| *n != 0)
+		.collect::<Vec<_>>();
+
+	memory.sort_unstable();
+
+	State {
+		pos: machine.pos,
+		base: machine.base,
+		memory,
+		outputs,
+		end,
+	}
+}
+
+fn state<M: Memory>(computer: &Computer<M>, io: Buffer, end: End) -> State {
+	State {
+		pos: computer.pos,
+		base: computer.base,
+		memory: computer
+			.cells()
+			.into_iter()
+			.filter(|(_, n)| *n != 0)
+			.collect(),
+		outputs: io.outputs,
+		end,
+	}
+}
+
+// Steps until `step` says the machine stopped, or the limit runs out
+fn drive<F>(limit: usize, mut step: F) -> End
+where
+	F: FnMut() -> Result<Option<Halt>, VmError>,
+{
+	for _ in 0..limit {
+		match step() {
+			Ok(None) => {}
+			Ok(Some(halt)) => return End::Halt(halt),
+			Err(err) => return End::Error(err),
+		}
+	}
+
+	End::Limit
+}
+
+fn dense(program: &[i64], inputs: &[i64], limit: usize) -> State {
+	let mut computer = Computer::new(program.to_vec());
+	let mut io = Buffer::new(inputs.to_vec());
+	let end = drive(limit, || computer.step(&mut io));
+
+	state(&computer, io, end)
+}
+
+fn sparse(program: &[i64], inputs: &[i64], limit: usize) -> State {
+	let mut computer = Computer::with_memory(Sparse::from(program.to_vec()));
+	let mut io = Buffer::new(inputs.to_vec());
+	let end = drive(limit, || computer.step(&mut io));
+
+	state(&computer, io, end)
+}
+
+fn cached(program: &[i64], inputs: &[i64], limit: usize) -> State {
+	let mut computer = Cached::from(Computer::new(program.to_vec()));
+	let mut io = Buffer::new(inputs.to_vec());
+	let end = drive(limit, || computer.step(&mut io));
+
+	state(computer.computer(), io, end)
+}
+
+fn profiled(program: &[i64], inputs: &[i64], limit: usize) -> State {
+	let mut computer = Computer::new(program.to_vec());
+	let mut io = Buffer::new(inputs.to_vec());
+	let mut profile = Profile::new();
+	let end = drive(limit, || computer.step_profiled(&mut io, &mut profile));
+
+	state(&computer, io, end)
+}
+
+fn logged(program: &[i64], inputs: &[i64], limit: usize) -> State {
+	let mut computer = Computer::new(program.to_vec());
+	let mut io = Buffer::new(inputs.to_vec());
+	let mut history = History::new();
+	let end = drive(limit, || computer.step_logged(&mut io, &mut history));
+
+	state(&computer, io, end)
+}
+
+fn debugger(program: &[i64], inputs: &[i64], limit: usize) -> State {
+	let mut debugger = Debugger::new(Computer::new(program.to_vec()));
+	let mut io = Buffer::new(inputs.to_vec());
+	let end = drive(limit, || match debugger.step(&mut io) {
+		Stop::Halt(halt) => Ok(Some(halt)),
+		Stop::Error(err) => Err(err),
+		_ => Ok(None),
+	});
+
+	state(&debugger.computer, io, end)
+}
+
+// Runs `runs` random programs, from `seed` on, through every engine and the
+// reference interpreter, returning the first that any of them disagree on.
+// Returns how many programs halted or failed before the limit, since those
+// are the ones whose final state is worth comparing. The transpiler is not
+// here because its output is Rust source that has to be compiled first; the
+// day 9 build script does that and its test checks the answers.
+pub fn fuzz(
+	seed: u64,
+	runs: u64,
+	limit: usize,
+) -> Result<usize, Box<Difference>> {
+	compare(&ENGINES, seed, runs, limit)
+}
+
+fn compare(
+	engines: &[(&'static str, Engine)],
+	seed: u64,
+	runs: u64,
+	limit: usize,
+) -> Result<usize, Box<Difference>> {
+	let mut stopped = 0;
+
+	for i in 0..runs {
+		let seed = seed.wrapping_add(i);
+		let (program, inputs) = generate(seed, 8 + (seed % 56) as usize);
+		let expected = reference(&program, &inputs, limit);
+
+		for (engine, run) in engines.iter() {
+			let actual = run(&program, &inputs, limit);
+
+			if actual != expected {
+				return Err(Box::new(Difference {
+					program,
+					inputs,
+					engine,
+					expected,
+					actual,
+				}));
+			}
+		}
+
+		if expected.end != End::Limit {
+			stopped += 1;
+		}
+	}
+
+	Ok(stopped)
+}
+
+#[test]
+fn test_reference() {
+	// The day 9 quine
+	let quine = vec![
+		109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0,
+		99,
+	];
+	let state = reference(&quine, &[], 1000);
+
+	assert_eq!(state.outputs, quine);
+	assert_eq!(state.end, End::Halt(Halt::Exit));
+	assert_eq!(state, dense(&quine, &[], 1000));
+
+	let overflow = [1102, 1 << 62, 4, 0, 99];
+
+	assert_eq!(
+		reference(&overflow, &[], 10).end,
+		End::Error(VmError::Overflow { addr: 0 })
+	);
+	assert_eq!(reference(&overflow, &[], 10), dense(&overflow, &[], 10));
+	assert_eq!(reference(&[1105, 1, 0], &[], 10).end, End::Limit);
+}
+
+#[test]
+fn test_fuzz() {
+	let stopped = fuzz(1, 500, 1000).unwrap();
+
+	assert!(stopped > 400, "only {} programs stopped", stopped);
+
+	// Wraps around instead of overflowing
+	assert!(fuzz(u64::MAX - 10, 20, 1000).is_ok());
+}
+
+#[test]
+fn test_fuzz_catches() {
+	// Forgets the last output
+	fn broken(program: &[i64], inputs: &[i64], limit: usize) -> State {
+		let mut state = dense(program, inputs, limit);
+
+		state.outputs.pop();
+		state
+	}
+
+	let difference = compare(&[("broken", broken)], 1, 500, 1000).unwrap_err();
+
+	assert_eq!(difference.engine, "broken");
+	assert!(!difference.expected.outputs.is_empty());
+}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use crate::computer::{Computer, Halt};
+use crate::error::VmError;
+use crate::io::Io;
+use crate::memory::Memory;
+
+#[cfg(test)]
+use crate::io::Buffer;
+
+// What one instruction changed, to put back when stepping over it backwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Change {
+	pos: usize,
+	base: i64,
+	write: Option<(usize, i64)>,
+}
+
+// An undo log of the instructions a machine has executed. Only the machine
+// is rewound: inputs it read and outputs it sent stay with the `Io`.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+	changes: VecDeque<Change>,
+	limit: Option<usize>,
+}
+
+impl History {
+	pub fn new() -> Self {
+		History::default()
+	}
+
+	// Forgets the oldest instructions beyond the last `limit`
+	pub fn with_limit(limit: usize) -> Self {
+		History {
+			changes: VecDeque::new(),
+			limit: Some(limit),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.changes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.changes.is_empty()
+	}
+
+	pub fn clear(&mut self) {
+		self.changes.clear();
+	}
+
+	fn push(&mut self, change: Change) {
+		if self.limit == Some(0) {
+			return;
+		}
+
+		if Some(self.changes.len()) == self.limit {
+			self.changes.pop_front();
+		}
+
+		self.changes.push_back(change);
+	}
+
+	// Rewinds the last instruction, returning its address
+	pub fn undo<M: Memory>(
+		&mut self,
+		computer: &mut Computer<M>,
+	) -> Option<usize> {
+		let change = self.changes.pop_back()?;
+
+		if let Some((addr, value)) = change.write {
+			computer.set(addr, value);
+		}
+
+		computer.pos = change.pos;
+		computer.base = change.base;
+
+		Some(change.pos)
+	}
+}
+
+impl<M: Memory> Computer<M> {
+	// A step that fails leaves the machine as it was, so nothing is logged
+	// for it
+	pub fn step_logged<T: Io>(
+		&mut self,
+		io: &mut T,
+		history: &mut History,
+	) -> Result<Option<Halt>, VmError> {
+		let write = self.write_target().map(|j| (j, self.get(j)));
+
+		let change = Change {
+			pos: self.pos,
+			base: self.base,
+			write,
+		};
+
+		let halt = self.step(io)?;
+
+		history.push(change);
+
+		Ok(halt)
+	}
+
+	pub fn run_logged<T: Io>(
+		&mut self,
+		io: &mut T,
+		history: &mut History,
+	) -> Result<Halt, VmError> {
+		loop {
+			if let Some(halt) = self.step_logged(io, history)? {
+				return Ok(halt);
+			}
+		}
+	}
+}
+
+#[test]
+fn test_undo() {
+	// Reads a number into [11], adds 10 to it, moves the base and outputs it
+	let mut computer = Computer::parse("3,11,1001,11,10,11,109,5,4,11,99,0");
+	let mut history = History::new();
+	let mut io = Buffer::new(vec![7]);
+
+	computer.run_logged(&mut io, &mut history).unwrap();
+
+	assert_eq!(io.outputs, vec![17]);
+	assert_eq!(history.len(), 5);
+
+	// Back over the halt and output, then the base change
+	assert_eq!(history.undo(&mut computer), Some(10));
+	assert_eq!(history.undo(&mut computer), Some(8));
+	assert_eq!((computer.pos, computer.base), (8, 5));
+	assert_eq!(history.undo(&mut computer), Some(6));
+	assert_eq!((computer.pos, computer.base), (6, 0));
+
+	// Then the add and input, restoring what they overwrote
+	assert_eq!(history.undo(&mut computer), Some(2));
+	assert_eq!(computer.get(11), 7);
+	assert_eq!(history.undo(&mut computer), Some(0));
+	assert_eq!(computer.get(11), 0);
+	assert_eq!(history.undo(&mut computer), None);
+
+	// Replaying gives the same result
+	computer.run(&mut Buffer::new(vec![7])).unwrap();
+
+	assert_eq!(computer.get(11), 17);
+}
+
+#[test]
+fn test_limit() {
+	let mut computer = Computer::parse("1001,9,-1,9,1005,9,0,99,0,3");
+	let mut history = History::with_limit(2);
+
+	computer
+		.run_logged(&mut Buffer::default(), &mut history)
+		.unwrap();
+
+	assert_eq!(history.len(), 2);
+	assert_eq!(history.undo(&mut computer), Some(7));
+	assert_eq!(history.undo(&mut computer), Some(4));
+	assert_eq!(history.undo(&mut computer), None);
+
+	// A failed step is not logged
+	let mut computer = Computer::parse("3,0,99");
+
+	assert!(computer
+		.step_logged(&mut Buffer::default(), &mut history)
+		.is_err());
+	assert!(history.is_empty());
+}
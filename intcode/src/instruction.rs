@@ -0,0 +1,228 @@
+use crate::error::VmError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+	Position,
+	Immediate,
+	Relative,
+}
+
+impl Mode {
+	pub fn parse(n: i64) -> Option<Self> {
+		match n {
+			0 => Some(Mode::Position),
+			1 => Some(Mode::Immediate),
+			2 => Some(Mode::Relative),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+	Add,
+	Multiply,
+	Input,
+	Output,
+	JumpTrue,
+	JumpFalse,
+	LessThan,
+	Equals,
+	IncrementBase,
+	Halt,
+}
+
+impl Opcode {
+	pub fn parse(n: i64) -> Option<Self> {
+		match n % 100 {
+			1 => Some(Opcode::Add),
+			2 => Some(Opcode::Multiply),
+			3 => Some(Opcode::Input),
+			4 => Some(Opcode::Output),
+			5 => Some(Opcode::JumpTrue),
+			6 => Some(Opcode::JumpFalse),
+			7 => Some(Opcode::LessThan),
+			8 => Some(Opcode::Equals),
+			9 => Some(Opcode::IncrementBase),
+			99 => Some(Opcode::Halt),
+			_ => None,
+		}
+	}
+
+	pub fn from_mnemonic(s: &str) -> Option<Self> {
+		let opcode = match s.to_uppercase().as_str() {
+			"ADD" => Opcode::Add,
+			"MUL" => Opcode::Multiply,
+			"IN" => Opcode::Input,
+			"OUT" => Opcode::Output,
+			"JT" => Opcode::JumpTrue,
+			"JF" => Opcode::JumpFalse,
+			"LT" => Opcode::LessThan,
+			"EQ" => Opcode::Equals,
+			"ARB" => Opcode::IncrementBase,
+			"HLT" => Opcode::Halt,
+			_ => return None,
+		};
+
+		Some(opcode)
+	}
+
+	pub fn code(self) -> i64 {
+		match self {
+			Opcode::Add => 1,
+			Opcode::Multiply => 2,
+			Opcode::Input => 3,
+			Opcode::Output => 4,
+			Opcode::JumpTrue => 5,
+			Opcode::JumpFalse => 6,
+			Opcode::LessThan => 7,
+			Opcode::Equals => 8,
+			Opcode::IncrementBase => 9,
+			Opcode::Halt => 99,
+		}
+	}
+
+	pub fn arity(self) -> usize {
+		match self {
+			Opcode::Add | Opcode::Multiply => 3,
+			Opcode::LessThan | Opcode::Equals => 3,
+			Opcode::JumpTrue | Opcode::JumpFalse => 2,
+			Opcode::Input | Opcode::Output => 1,
+			Opcode::IncrementBase => 1,
+			Opcode::Halt => 0,
+		}
+	}
+
+	pub fn target(self) -> Option<usize> {
+		match self {
+			Opcode::Add | Opcode::Multiply => Some(2),
+			Opcode::LessThan | Opcode::Equals => Some(2),
+			Opcode::Input => Some(0),
+			_ => None,
+		}
+	}
+
+	pub fn mnemonic(self) -> &'static str {
+		match self {
+			Opcode::Add => "ADD",
+			Opcode::Multiply => "MUL",
+			Opcode::Input => "IN",
+			Opcode::Output => "OUT",
+			Opcode::JumpTrue => "JT",
+			Opcode::JumpFalse => "JF",
+			Opcode::LessThan => "LT",
+			Opcode::Equals => "EQ",
+			Opcode::IncrementBase => "ARB",
+			Opcode::Halt => "HLT",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instruction {
+	pub opcode: Opcode,
+	pub modes: [Mode; 3],
+}
+
+impl Instruction {
+	// The same words the VM will run: mode digits past the opcode's arity
+	// are ignored rather than rejected
+	pub fn parse(n: i64) -> Option<Self> {
+		Instruction::decode(n, 0).ok()
+	}
+
+	pub fn decode(n: i64, addr: usize) -> Result<Self, VmError> {
+		let opcode = Opcode::parse(n).ok_or(VmError::InvalidOpcode {
+			addr,
+			opcode: n % 100,
+		})?;
+
+		let mut modes = [Mode::Position; 3];
+
+		for (i, mode) in modes.iter_mut().enumerate().take(opcode.arity()) {
+			let n = n / i64::pow(10, i as u32 + 2) % 10;
+
+			*mode =
+				Mode::parse(n).ok_or(VmError::InvalidMode { addr, mode: n })?;
+		}
+
+		Ok(Instruction { opcode, modes })
+	}
+
+	pub fn encode(&self) -> i64 {
+		self.modes
+			.iter()
+			.enumerate()
+			.map(|(i, mode)| {
+				let mode = match mode {
+					Mode::Position => 0,
+					Mode::Immediate => 1,
+					Mode::Relative => 2,
+				};
+
+				mode * i64::pow(10, i as u32 + 2)
+			})
+			.sum::<i64>()
+			+ self.opcode.code()
+	}
+
+	pub fn size(&self) -> usize {
+		self.opcode.arity() + 1
+	}
+}
+
+#[test]
+fn test_decode() {
+	let instruction = Instruction::decode(1002, 0).unwrap();
+
+	assert_eq!(instruction.opcode, Opcode::Multiply);
+	assert_eq!(
+		instruction.modes,
+		[Mode::Position, Mode::Immediate, Mode::Position]
+	);
+
+	let instruction = Instruction::decode(21101, 0).unwrap();
+
+	assert_eq!(instruction.opcode, Opcode::Add);
+	assert_eq!(
+		instruction.modes,
+		[Mode::Immediate, Mode::Immediate, Mode::Relative]
+	);
+}
+
+#[test]
+fn test_decode_errors() {
+	assert_eq!(
+		Instruction::decode(42, 7),
+		Err(VmError::InvalidOpcode {
+			addr: 7,
+			opcode: 42
+		})
+	);
+
+	assert_eq!(
+		Instruction::decode(1301, 3),
+		Err(VmError::InvalidMode { addr: 3, mode: 3 })
+	);
+
+	assert!(Instruction::decode(30004, 0).is_ok());
+}
+
+#[test]
+fn test_encode() {
+	for n in [1002, 21101, 3, 204, 1105, 99] {
+		assert_eq!(Instruction::decode(n, 0).unwrap().encode(), n);
+	}
+}
+
+#[test]
+fn test_parse() {
+	assert_eq!(Instruction::parse(1002), Instruction::decode(1002, 0).ok());
+	assert_eq!(Instruction::parse(0), None);
+	assert_eq!(Instruction::parse(301), None);
+	assert_eq!(Instruction::parse(-1), None);
+
+	for n in [1099, 100_001, 30004] {
+		assert_eq!(Instruction::parse(n), Instruction::decode(n, 0).ok());
+	}
+}
@@ -0,0 +1,39 @@
+use std::collections::VecDeque;
+
+pub trait Io {
+	fn input(&mut self) -> Option<i64>;
+	fn output(&mut self, n: i64);
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Buffer {
+	pub inputs: VecDeque<i64>,
+	pub outputs: Vec<i64>,
+}
+
+impl Buffer {
+	pub fn new(inputs: Vec<i64>) -> Self {
+		Buffer {
+			inputs: inputs.into(),
+			outputs: Vec::new(),
+		}
+	}
+
+	pub fn push(&mut self, n: i64) {
+		self.inputs.push_back(n);
+	}
+
+	pub fn drain(&mut self) -> Vec<i64> {
+		std::mem::take(&mut self.outputs)
+	}
+}
+
+impl Io for Buffer {
+	fn input(&mut self) -> Option<i64> {
+		self.inputs.pop_front()
+	}
+
+	fn output(&mut self, n: i64) {
+		self.outputs.push(n);
+	}
+}
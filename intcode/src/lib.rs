@@ -0,0 +1,58 @@
+mod ascii;
+mod asm;
+mod budget;
+mod cache;
+mod cfg;
+mod channel;
+mod computer;
+mod debugger;
+mod device;
+mod disasm;
+mod error;
+mod fuzz;
+mod history;
+mod instruction;
+mod io;
+mod memory;
+mod profile;
+mod scheduler;
+mod snapshot;
+mod solver;
+mod trace;
+mod transpile;
+mod watch;
+
+pub use ascii::{decode, encode, Chunk, Terminal};
+pub use asm::{assemble, AsmError};
+pub use budget::{Budget, Outcome};
+pub use cache::Cached;
+pub use cfg::{Block, Cfg, Edge};
+pub use channel::Channel;
+pub use computer::{Computer, Halt};
+pub use debugger::{Breakpoint, Debugger, Stop};
+pub use device::{Bus, Device};
+pub use disasm::{disassemble, Line, Listing, Param};
+pub use error::VmError;
+pub use fuzz::{fuzz, generate, reference, Difference, End, State};
+pub use history::History;
+pub use instruction::{Instruction, Mode, Opcode};
+pub use io::{Buffer, Io};
+pub use memory::{Dense, Memory, Sparse};
+pub use profile::Profile;
+pub use scheduler::{MachineError, Packet, Report, Schedule, Scheduler};
+pub use snapshot::{
+	join_list, parse_list, restore, save, Persist, SnapshotError,
+};
+pub use solver::Solver;
+pub use trace::{inputs, record, replay, Entry, Event, Mismatch, TraceError};
+pub use transpile::transpile;
+pub use watch::{Access, Action, Hit, Watcher};
+
+pub fn parse(input: &str) -> Vec<i64> {
+	input
+		.trim()
+		.split(',')
+		.map(|n| n.trim().parse::<i64>())
+		.collect::<Result<Vec<_>, _>>()
+		.expect("Failed to parse number")
+}
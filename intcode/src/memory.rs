@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::fmt;
+
+#[cfg(test)]
+use crate::computer::Computer;
+#[cfg(test)]
+use crate::io::Buffer;
+
+// Cells beyond this are kept in a map, so that a stray write to a huge address
+// does not allocate all the memory below it.
+const DENSE_LIMIT: usize = 1 << 20;
+
+pub trait Memory: Clone + fmt::Debug + Default + From<Vec<i64>> {
+	fn get(&self, i: usize) -> i64;
+	fn set(&mut self, i: usize, n: i64);
+
+	// Every cell needed to reproduce the memory, in address order. That is
+	// the whole program, and any non-zero cell past it.
+	fn cells(&self) -> Vec<(usize, i64)>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Sparse {
+	cells: HashMap<usize, i64>,
+	len: usize,
+}
+
+impl From<Vec<i64>> for Sparse {
+	fn from(nums: Vec<i64>) -> Self {
+		Sparse {
+			len: nums.len(),
+			cells: nums.into_iter().enumerate().collect(),
+		}
+	}
+}
+
+impl Memory for Sparse {
+	fn get(&self, i: usize) -> i64 {
+		self.cells.get(&i).cloned().unwrap_or(0)
+	}
+
+	fn set(&mut self, i: usize, n: i64) {
+		self.cells.insert(i, n);
+	}
+
+	fn cells(&self) -> Vec<(usize, i64)> {
+		let mut cells = self
+			.cells
+			.iter()
+			.map(|(i, n)| (*i, *n))
+			.filter(|(i, n)| *i < self.len || *n != 0)
+			.collect::<Vec<_>>();
+
+		cells.sort_unstable();
+		cells
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Dense {
+	cells: Vec<i64>,
+	len: usize,
+	far: HashMap<usize, i64>,
+}
+
+impl From<Vec<i64>> for Dense {
+	fn from(nums: Vec<i64>) -> Self {
+		Dense {
+			len: nums.len(),
+			cells: nums,
+			far: HashMap::new(),
+		}
+	}
+}
+
+impl Memory for Dense {
+	fn get(&self, i: usize) -> i64 {
+		match self.cells.get(i) {
+			Some(n) => *n,
+			None if i < DENSE_LIMIT => 0,
+			None => self.far.get(&i).cloned().unwrap_or(0),
+		}
+	}
+
+	fn set(&mut self, i: usize, n: i64) {
+		if i < self.cells.len() {
+			self.cells[i] = n;
+		} else if i < DENSE_LIMIT {
+			// Grow geometrically, zero cells read the same either way
+			if n != 0 {
+				let len = (i + 1).max(self.cells.len() * 2).min(DENSE_LIMIT);
+
+				self.cells.resize(len, 0);
+				self.cells[i] = n;
+			}
+		} else {
+			self.far.insert(i, n);
+		}
+	}
+
+	// The zeros the vector grew by are not part of the program
+	fn cells(&self) -> Vec<(usize, i64)> {
+		let mut far = self
+			.far
+			.iter()
+			.map(|(i, n)| (*i, *n))
+			.filter(|(_, n)| *n != 0)
+			.collect::<Vec<_>>();
+
+		far.sort_unstable();
+
+		self.cells
+			.iter()
+			.cloned()
+			.enumerate()
+			.filter(|(i, n)| *i < self.len || *n != 0)
+			.chain(far)
+			.collect()
+	}
+}
+
+#[test]
+fn test_dense() {
+	let mut memory = Dense::from(vec![1, 0, 2]);
+
+	memory.set(10, 5);
+	memory.set(20, 0);
+	memory.set(DENSE_LIMIT + 1, 7);
+
+	assert_eq!(memory.get(1), 0);
+	assert_eq!(memory.get(10), 5);
+	assert_eq!(memory.get(20), 0);
+	assert_eq!(memory.get(DENSE_LIMIT + 1), 7);
+	assert!(memory.cells.len() < DENSE_LIMIT);
+	assert_eq!(
+		memory.cells(),
+		vec![(0, 1), (1, 0), (2, 2), (10, 5), (DENSE_LIMIT + 1, 7)]
+	);
+}
+
+#[test]
+fn test_backends() {
+	let nums = vec![3, 0, 0, 1, 99];
+	let mut dense = Dense::from(nums.clone());
+	let mut sparse = Sparse::from(nums);
+
+	for (i, n) in [(1, 4), (500, 9), (3, 0)] {
+		dense.set(i, n);
+		sparse.set(i, n);
+	}
+
+	assert_eq!(dense.cells(), sparse.cells());
+}
+
+#[test]
+fn test_backends_zeros() {
+	let nums = vec![1101, 0, 0, 10, 99];
+	let mut dense = Computer::new(nums.clone());
+	let mut sparse = Computer::with_memory(Sparse::from(nums.clone()));
+
+	dense.run(&mut Buffer::default()).unwrap();
+	sparse.run(&mut Buffer::default()).unwrap();
+
+	assert_eq!(dense.cells(), sparse.cells());
+	assert_eq!(dense.memory(), nums);
+	assert_eq!(sparse.memory(), nums);
+
+	for (i, n) in [(DENSE_LIMIT + 1, 7), (DENSE_LIMIT + 1, 0), (20, 0)] {
+		dense.set(i, n);
+		sparse.set(i, n);
+	}
+
+	assert_eq!(dense.cells(), sparse.cells());
+}
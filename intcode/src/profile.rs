@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::computer::{Computer, Halt};
+use crate::disasm::Line;
+use crate::error::VmError;
+use crate::instruction::{Instruction, Opcode};
+use crate::io::Io;
+use crate::memory::Memory;
+
+#[cfg(test)]
+use crate::io::Buffer;
+
+// Deeper call chains reuse the innermost frame, so that a jump pattern the
+// call heuristic gets wrong cannot grow the stack without bound
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Frame {
+	entry: usize,
+	ret: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Profile {
+	pub steps: u64,
+	pub executions: HashMap<usize, u64>,
+	pub opcodes: HashMap<Opcode, u64>,
+	pub reads: HashMap<usize, u64>,
+	pub writes: HashMap<usize, u64>,
+	stacks: HashMap<Vec<usize>, u64>,
+	frames: Vec<Frame>,
+	written: Option<i64>,
+}
+
+impl Default for Profile {
+	fn default() -> Self {
+		Profile {
+			steps: 0,
+			executions: HashMap::new(),
+			opcodes: HashMap::new(),
+			reads: HashMap::new(),
+			writes: HashMap::new(),
+			stacks: HashMap::new(),
+			frames: vec![Frame { entry: 0, ret: 0 }],
+			written: None,
+		}
+	}
+}
+
+fn ranked<K: Copy + Ord>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+	let mut ranked = counts.iter().map(|(k, n)| (*k, *n)).collect::<Vec<_>>();
+
+	ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+	ranked
+}
+
+impl Profile {
+	pub fn new() -> Self {
+		Profile::default()
+	}
+
+	// Compiled Intcode calls a function by writing the address after the jump
+	// just before taking it, and returns by jumping to that address. Any
+	// other jump is a branch within the current function.
+	fn jump(&mut self, from: usize, to: usize, size: usize) {
+		if let Some(i) = self.frames.iter().skip(1).rposition(|f| f.ret == to) {
+			self.frames.truncate(i + 1);
+			return;
+		}
+
+		if self.written != Some((from + size) as i64) {
+			return;
+		}
+
+		let frame = Frame {
+			entry: to,
+			ret: from + size,
+		};
+
+		if self.frames.len() < MAX_DEPTH {
+			self.frames.push(frame);
+		} else if let Some(last) = self.frames.last_mut() {
+			*last = frame;
+		}
+	}
+
+	pub fn report<M: Memory>(
+		&self,
+		computer: &Computer<M>,
+		n: usize,
+	) -> String {
+		let mut report = String::new();
+		let percent =
+			|count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+
+		let _ = writeln!(report, "{} instructions executed", self.steps);
+		let _ = writeln!(report, "\nHottest instructions:");
+
+		for (addr, count) in ranked(&self.executions).into_iter().take(n) {
+			let line = Line::decode(|i| computer.get(i), addr);
+
+			let _ = writeln!(
+				report,
+				"{:>12} {:>6.2}% {:>6}: {}",
+				count,
+				percent(count),
+				addr,
+				line
+			);
+		}
+
+		let _ = writeln!(report, "\nOpcodes:");
+
+		let mut opcodes = self.opcodes.iter().collect::<Vec<_>>();
+
+		opcodes.sort_unstable_by(|a, b| {
+			b.1.cmp(a.1).then(a.0.code().cmp(&b.0.code()))
+		});
+
+		for (opcode, count) in opcodes {
+			let _ = writeln!(
+				report,
+				"{:>12} {:>6.2}% {}",
+				count,
+				percent(*count),
+				opcode.mnemonic()
+			);
+		}
+
+		for (title, counts) in
+			[("reads", &self.reads), ("writes", &self.writes)]
+		{
+			let _ = writeln!(report, "\nMost {}:", title);
+
+			for (addr, count) in ranked(counts).into_iter().take(n) {
+				let _ = writeln!(
+					report,
+					"{:>12} [{}] = {}",
+					count,
+					addr,
+					computer.get(addr)
+				);
+			}
+		}
+
+		report
+	}
+
+	// One line per call stack, `0;123;456 42`, as read by flamegraph tools
+	pub fn folded(&self) -> String {
+		let mut stacks = self
+			.stacks
+			.iter()
+			.map(|(stack, count)| {
+				let frames =
+					stack.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+				format!("{} {}", frames.join(";"), count)
+			})
+			.collect::<Vec<_>>();
+
+		stacks.sort_unstable();
+		stacks.join("\n") + "\n"
+	}
+}
+
+impl<M: Memory> Computer<M> {
+	pub fn step_profiled<T: Io>(
+		&mut self,
+		io: &mut T,
+		profile: &mut Profile,
+	) -> Result<Option<Halt>, VmError> {
+		let pos = self.pos;
+		let instruction = Instruction::decode(self.get(pos), pos)?;
+		let target = instruction.opcode.target();
+
+		let operands = self.operands(instruction);
+		let stack = profile.frames.iter().map(|f| f.entry).collect();
+		let halt = self.step(io)?;
+
+		profile.steps += 1;
+		*profile.executions.entry(pos).or_insert(0) += 1;
+		*profile.opcodes.entry(instruction.opcode).or_insert(0) += 1;
+		*profile.stacks.entry(stack).or_insert(0) += 1;
+
+		if self.pos != pos + instruction.size() && halt.is_none() {
+			profile.jump(pos, self.pos, instruction.size());
+		}
+
+		profile.written = None;
+
+		for (i, addr) in operands {
+			let heat = match Some(i) == target {
+				true => &mut profile.writes,
+				false => &mut profile.reads,
+			};
+
+			*heat.entry(addr).or_insert(0) += 1;
+
+			if Some(i) == target {
+				profile.written = Some(self.get(addr));
+			}
+		}
+
+		Ok(halt)
+	}
+
+	pub fn run_profiled<T: Io>(
+		&mut self,
+		io: &mut T,
+		profile: &mut Profile,
+	) -> Result<Halt, VmError> {
+		loop {
+			if let Some(halt) = self.step_profiled(io, profile)? {
+				return Ok(halt);
+			}
+		}
+	}
+}
+
+#[test]
+fn test_profile() {
+	// Calls a subroutine at 18 twice, passing the address to return to in [30]
+	let mut computer = Computer::parse(
+		"1101,0,7,30,1105,1,18,1101,0,14,30,1105,1,18,99,0,0,0,\
+		 104,7,105,1,30,0,0,0,0,0,0,0,0",
+	);
+	let mut profile = Profile::new();
+	let mut io = Buffer::default();
+
+	computer.run_profiled(&mut io, &mut profile).unwrap();
+
+	assert_eq!(io.outputs, vec![7, 7]);
+	assert_eq!(profile.steps, 9);
+	assert_eq!(profile.executions[&18], 2);
+	assert_eq!(profile.opcodes[&Opcode::JumpTrue], 4);
+	assert_eq!(profile.reads[&30], 2);
+	assert_eq!(profile.writes[&30], 2);
+	assert_eq!(profile.folded(), "0 5\n0;18 4\n");
+
+	let report = profile.report(&computer, 3);
+
+	assert!(report.starts_with("9 instructions executed"));
+	assert!(report.contains("18: OUT #7"));
+}
+
+#[test]
+fn test_loop() {
+	// Counts [9] down from 3, jumping back within the same frame
+	let mut computer = Computer::parse("1001,9,-1,9,1005,9,0,99,0,3");
+	let mut profile = Profile::new();
+
+	computer
+		.run_profiled(&mut Buffer::default(), &mut profile)
+		.unwrap();
+
+	assert_eq!(profile.executions[&0], 3);
+	assert_eq!(profile.folded(), "0 7\n");
+}
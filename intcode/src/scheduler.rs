@@ -0,0 +1,445 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::thread;
+
+use crate::computer::Computer;
+use crate::error::VmError;
+use crate::io::Io;
+use crate::memory::{Dense, Memory};
+
+#[cfg(test)]
+use crate::asm::assemble;
+
+// What a machine reads when no packet is waiting for it
+const EMPTY: i64 = -1;
+
+// How many instructions each machine runs per round by default
+const SLICE: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Packet {
+	pub dest: i64,
+	pub x: i64,
+	pub y: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Report {
+	// A packet sent to the monitor address
+	Monitor(Packet),
+	// A packet sent to an address nothing is listening on
+	Stray(Packet),
+	// Every machine is waiting on an empty queue and nothing is in flight
+	Idle,
+	// Every machine has halted
+	Halted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule {
+	RoundRobin,
+	// Splits the machines between this many threads each round
+	Threads(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MachineError {
+	pub machine: usize,
+	pub error: VmError,
+}
+
+impl fmt::Display for MachineError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "machine {}: {}", self.machine, self.error)
+	}
+}
+
+impl std::error::Error for MachineError {}
+
+#[derive(Clone, Debug, Default)]
+struct Port {
+	inbox: VecDeque<i64>,
+	outbox: Vec<i64>,
+	// Empty reads in a row
+	starved: usize,
+	waiting: bool,
+}
+
+impl Io for Port {
+	fn input(&mut self) -> Option<i64> {
+		match self.inbox.pop_front() {
+			Some(n) => {
+				self.starved = 0;
+				Some(n)
+			}
+			None => {
+				self.starved += 1;
+				self.waiting = true;
+				Some(EMPTY)
+			}
+		}
+	}
+
+	fn output(&mut self, n: i64) {
+		self.starved = 0;
+		self.outbox.push(n);
+	}
+}
+
+#[derive(Clone, Debug)]
+struct Machine<M: Memory> {
+	computer: Computer<M>,
+	port: Port,
+	halted: bool,
+}
+
+impl<M: Memory> Machine<M> {
+	// Runs until the machine halts, finds its queue empty, or has used up
+	// its slice. One that used its whole slice without finding the queue
+	// empty is busy, however many empty reads came before.
+	fn slice(&mut self, steps: usize) -> Result<(), VmError> {
+		self.port.waiting = false;
+
+		for _ in 0..steps {
+			if self.halted || self.port.waiting {
+				break;
+			}
+
+			self.halted = self.computer.step(&mut self.port)?.is_some();
+		}
+
+		if !self.port.waiting {
+			self.port.starved = 0;
+		}
+
+		Ok(())
+	}
+
+	fn idle(&self) -> bool {
+		self.halted
+			|| (self.port.starved >= 2
+				&& self.port.inbox.is_empty()
+				&& self.port.outbox.is_empty())
+	}
+}
+
+// Runs machines that talk by sending each other `(dest, x, y)` packets, with
+// each machine reading its own address first and then `-1` whenever no packet
+// is waiting. Machines run in rounds, and the packets sent in a round are
+// delivered once it ends, so a run goes the same way however the machines
+// are scheduled.
+#[derive(Clone, Debug)]
+pub struct Scheduler<M: Memory = Dense> {
+	machines: Vec<Machine<M>>,
+	schedule: Schedule,
+	slice: usize,
+	monitor: Option<i64>,
+	broadcast: Option<i64>,
+	reports: VecDeque<Report>,
+}
+
+impl<M: Memory + Send> Scheduler<M> {
+	pub fn new(computers: Vec<Computer<M>>) -> Self {
+		let machines = computers
+			.into_iter()
+			.enumerate()
+			.map(|(i, computer)| {
+				let mut port = Port::default();
+
+				port.inbox.push_back(i as i64);
+
+				Machine {
+					computer,
+					port,
+					halted: false,
+				}
+			})
+			.collect();
+
+		Scheduler {
+			machines,
+			schedule: Schedule::RoundRobin,
+			slice: SLICE,
+			monitor: None,
+			broadcast: None,
+			reports: VecDeque::new(),
+		}
+	}
+
+	pub fn schedule(&mut self, schedule: Schedule) -> &mut Self {
+		self.schedule = schedule;
+		self
+	}
+
+	pub fn slice(&mut self, steps: usize) -> &mut Self {
+		self.slice = steps.max(1);
+		self
+	}
+
+	// Packets sent here are handed back from `run`
+	pub fn monitor(&mut self, addr: i64) -> &mut Self {
+		self.monitor = Some(addr);
+		self
+	}
+
+	// Packets sent here go to every machine, the sender included
+	pub fn broadcast(&mut self, addr: i64) -> &mut Self {
+		self.broadcast = Some(addr);
+		self
+	}
+
+	pub fn computer(&self, i: usize) -> &Computer<M> {
+		&self.machines[i].computer
+	}
+
+	pub fn send(&mut self, packet: Packet) {
+		let dest = Some(packet.dest);
+
+		if dest == self.monitor {
+			self.reports.push_back(Report::Monitor(packet));
+		} else if dest == self.broadcast {
+			for machine in self.machines.iter_mut() {
+				machine.port.inbox.extend([packet.x, packet.y]);
+			}
+		} else if (0..self.machines.len() as i64).contains(&packet.dest) {
+			let machine = &mut self.machines[packet.dest as usize];
+
+			machine.port.inbox.extend([packet.x, packet.y]);
+		} else {
+			self.reports.push_back(Report::Stray(packet));
+		}
+	}
+
+	fn round(&mut self) -> Result<(), MachineError> {
+		let steps = self.slice;
+		let run = |offset: usize, machines: &mut [Machine<M>]| {
+			for (i, machine) in machines.iter_mut().enumerate() {
+				machine.slice(steps).map_err(|error| MachineError {
+					machine: offset + i,
+					error,
+				})?;
+			}
+
+			Ok(())
+		};
+
+		match self.schedule {
+			Schedule::RoundRobin => run(0, &mut self.machines),
+			Schedule::Threads(threads) => {
+				let size = self.machines.len().div_ceil(threads.max(1)).max(1);
+
+				thread::scope(|scope| {
+					let handles = self
+						.machines
+						.chunks_mut(size)
+						.enumerate()
+						.map(|(i, chunk)| {
+							scope.spawn(move || run(i * size, chunk))
+						})
+						.collect::<Vec<_>>();
+
+					handles.into_iter().try_for_each(|handle| {
+						handle.join().expect("Machine panicked")
+					})
+				})
+			}
+		}
+	}
+
+	// Runs rounds until something happens that the caller should know about.
+	// After `Idle` the network is usually stuck until a packet is sent into
+	// it. A machine failing stops everything.
+	pub fn run(&mut self) -> Result<Report, MachineError> {
+		loop {
+			if let Some(report) = self.reports.pop_front() {
+				return Ok(report);
+			}
+
+			if self.machines.iter().all(|machine| machine.halted) {
+				return Ok(Report::Halted);
+			}
+
+			self.round()?;
+
+			let mut packets = Vec::new();
+
+			for machine in self.machines.iter_mut() {
+				let n = machine.port.outbox.len() / 3 * 3;
+				let sent = machine.port.outbox.drain(..n).collect::<Vec<_>>();
+
+				for packet in sent.chunks(3) {
+					packets.push(Packet {
+						dest: packet[0],
+						x: packet[1],
+						y: packet[2],
+					});
+				}
+			}
+
+			let idle = packets.is_empty()
+				&& self.machines.iter().all(|machine| machine.idle())
+				&& self.machines.iter().any(|machine| !machine.halted);
+
+			for packet in packets {
+				self.send(packet);
+			}
+
+			if idle && self.reports.is_empty() {
+				return Ok(Report::Idle);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+const TOKEN: &str = "
+		IN -> [addr]
+	loop:
+		IN -> [x]
+		EQ [x], #-1 -> [t]
+		JT [t], #loop
+		IN -> [y]
+		JF [x], #done
+		; Passes the token on with one less in x and one more in y
+		ADD [x], #-1 -> [x]
+		ADD [y], #1 -> [y]
+		ADD [addr], #1 -> [next]
+		EQ [next], #4 -> [t]
+		JF [t], #send
+		ADD #0, #0 -> [next]
+	send:
+		OUT [next]
+		OUT [x]
+		OUT [y]
+		JT #1, #loop
+		; Reports where the token stopped and how far it went
+	done:
+		OUT #255
+		OUT [addr]
+		OUT [y]
+		JT #1, #loop
+	addr: .data 0
+	next: .data 0
+	x: .data 0
+	y: .data 0
+	t: .data 0
+";
+
+#[test]
+fn test_scheduler() {
+	let computer = Computer::new(assemble(TOKEN).unwrap());
+
+	for schedule in [Schedule::RoundRobin, Schedule::Threads(3)] {
+		let mut scheduler = Scheduler::new(vec![computer.clone(); 4]);
+
+		scheduler.schedule(schedule).monitor(255).slice(5);
+		scheduler.send(Packet {
+			dest: 0,
+			x: 10,
+			y: 0,
+		});
+
+		assert_eq!(
+			scheduler.run(),
+			Ok(Report::Monitor(Packet {
+				dest: 255,
+				x: 2,
+				y: 10
+			}))
+		);
+		assert_eq!(scheduler.run(), Ok(Report::Idle));
+		assert_eq!(scheduler.run(), Ok(Report::Idle));
+	}
+
+	let mut scheduler = Scheduler::new(vec![Computer::parse("99")]);
+
+	assert_eq!(scheduler.run(), Ok(Report::Halted));
+
+	let mut scheduler = Scheduler::new(vec![Computer::parse("3,0,3,0,0")]);
+
+	assert_eq!(
+		scheduler.run(),
+		Err(MachineError {
+			machine: 0,
+			error: VmError::InvalidOpcode { addr: 4, opcode: 0 }
+		})
+	);
+}
+
+#[test]
+fn test_busy() {
+	// Polls twice, then counts to 50 with no I/O before reporting the count
+	let program = "
+			IN -> [n]
+			IN -> [n]
+			IN -> [n]
+			ADD #0, #0 -> [n]
+		count:
+			ADD [n], #1 -> [n]
+			LT [n], #50 -> [t]
+			JT [t], #count
+			OUT #255
+			OUT [n]
+			OUT #0
+		wait:
+			IN -> [t]
+			JT #1, #wait
+		n: .data 0
+		t: .data 0
+	";
+	let computer = Computer::new(assemble(program).unwrap());
+	let mut scheduler = Scheduler::new(vec![computer]);
+
+	scheduler.monitor(255).slice(5);
+
+	// It looks idle after the second poll, but not once it is counting
+	assert_eq!(scheduler.run(), Ok(Report::Idle));
+	assert_eq!(
+		scheduler.run(),
+		Ok(Report::Monitor(Packet {
+			dest: 255,
+			x: 50,
+			y: 0
+		}))
+	);
+	assert_eq!(scheduler.run(), Ok(Report::Idle));
+}
+
+#[test]
+fn test_broadcast() {
+	let computer = Computer::new(assemble(TOKEN).unwrap());
+	let mut scheduler = Scheduler::new(vec![computer; 4]);
+
+	scheduler.monitor(255).broadcast(100);
+	scheduler.send(Packet {
+		dest: 100,
+		x: 1,
+		y: 0,
+	});
+
+	for i in 0..4 {
+		assert_eq!(
+			scheduler.run(),
+			Ok(Report::Monitor(Packet {
+				dest: 255,
+				x: i,
+				y: 1
+			}))
+		);
+	}
+
+	scheduler.send(Packet {
+		dest: 7,
+		x: 1,
+		y: 2,
+	});
+
+	assert_eq!(
+		scheduler.run(),
+		Ok(Report::Stray(Packet {
+			dest: 7,
+			x: 1,
+			y: 2
+		}))
+	);
+}
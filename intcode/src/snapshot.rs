@@ -0,0 +1,226 @@
+use std::fmt;
+
+use crate::computer::Computer;
+use crate::io::Buffer;
+use crate::memory::Memory;
+
+#[cfg(test)]
+use crate::memory::Dense;
+
+const SEPARATOR: &str = "---";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotError(pub String);
+
+impl fmt::Display for SnapshotError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Invalid snapshot: {}", self.0)
+	}
+}
+
+impl std::error::Error for SnapshotError {}
+
+pub trait Persist: Sized {
+	fn save(&self) -> String;
+	fn restore(state: &str) -> Result<Self, SnapshotError>;
+}
+
+pub fn parse_list(s: &str) -> Result<Vec<i64>, SnapshotError> {
+	s.split(',')
+		.map(str::trim)
+		.filter(|n| !n.is_empty())
+		.map(|n| {
+			n.parse::<i64>()
+				.map_err(|_| SnapshotError(format!("Invalid number: {}", n)))
+		})
+		.collect()
+}
+
+pub fn join_list<'a, I>(nums: I) -> String
+where
+	I: IntoIterator<Item = &'a i64>,
+{
+	nums.into_iter()
+		.map(|n| n.to_string())
+		.collect::<Vec<_>>()
+		.join(",")
+}
+
+// Memory is written as runs of consecutive addresses, one run per line, so
+// the sparse cells a program touches far past its end stay compact.
+impl<M: Memory> Persist for Computer<M> {
+	fn save(&self) -> String {
+		let mut lines =
+			vec![format!("pos {}", self.pos), format!("base {}", self.base)];
+
+		let mut run: Option<(usize, Vec<i64>)> = None;
+
+		for (i, n) in self.cells() {
+			match run.as_mut() {
+				Some((start, values)) if *start + values.len() == i => {
+					values.push(n)
+				}
+				_ => {
+					if let Some((start, values)) = run.replace((i, vec![n])) {
+						lines.push(format!(
+							"mem {} {}",
+							start,
+							join_list(&values)
+						));
+					}
+				}
+			}
+		}
+
+		if let Some((start, values)) = run {
+			lines.push(format!("mem {} {}", start, join_list(&values)));
+		}
+
+		lines.join("\n")
+	}
+
+	// The run at address 0 is loaded as the program, so that backends which
+	// only keep non-zero cells past the program restore the same cells.
+	fn restore(state: &str) -> Result<Self, SnapshotError> {
+		let (mut pos, mut base) = (0, 0);
+		let mut runs = Vec::new();
+
+		for line in state.lines().map(str::trim).filter(|l| !l.is_empty()) {
+			let mut parts = line.splitn(3, ' ');
+			let key = parts.next().unwrap_or("");
+			let mut next = || {
+				parts.next().ok_or_else(|| {
+					SnapshotError(format!("Incomplete line: {}", line))
+				})
+			};
+
+			let invalid = |_| SnapshotError(format!("Invalid line: {}", line));
+
+			match key {
+				"pos" => pos = next()?.parse().map_err(invalid)?,
+				"base" => base = next()?.parse().map_err(invalid)?,
+				"mem" => {
+					let start = next()?.parse::<usize>().map_err(invalid)?;
+
+					runs.push((start, parse_list(next()?)?));
+				}
+				_ => {
+					return Err(SnapshotError(format!("Unknown key: {}", key)))
+				}
+			}
+		}
+
+		let program = match runs.iter().position(|(start, _)| *start == 0) {
+			Some(i) => runs.remove(i).1,
+			None => Vec::new(),
+		};
+
+		let mut computer = Computer::with_memory(M::from(program));
+
+		for (start, values) in runs {
+			for (i, n) in values.into_iter().enumerate() {
+				let addr = start.checked_add(i).ok_or_else(|| {
+					SnapshotError(format!("Run at {} is too long", start))
+				})?;
+
+				computer.set(addr, n);
+			}
+		}
+
+		computer.pos = pos;
+		computer.base = base;
+
+		Ok(computer)
+	}
+}
+
+impl Persist for Buffer {
+	fn save(&self) -> String {
+		let inputs = format!("inputs {}", join_list(&self.inputs));
+		let outputs = format!("outputs {}", join_list(&self.outputs));
+
+		format!("{}\n{}", inputs.trim_end(), outputs.trim_end())
+	}
+
+	fn restore(state: &str) -> Result<Self, SnapshotError> {
+		let mut buffer = Buffer::default();
+
+		for line in state.lines().map(str::trim).filter(|l| !l.is_empty()) {
+			let (key, values) = match line.find(' ') {
+				Some(n) => (&line[..n], &line[(n + 1)..]),
+				None => (line, ""),
+			};
+
+			match key {
+				"inputs" => buffer.inputs = parse_list(values)?.into(),
+				"outputs" => buffer.outputs = parse_list(values)?,
+				_ => {
+					return Err(SnapshotError(format!("Unknown key: {}", key)))
+				}
+			}
+		}
+
+		Ok(buffer)
+	}
+}
+
+pub fn save<M: Memory, T: Persist>(
+	computer: &Computer<M>,
+	device: &T,
+) -> String {
+	format!("{}\n{}\n{}\n", computer.save(), SEPARATOR, device.save())
+}
+
+pub fn restore<T: Persist>(
+	snapshot: &str,
+) -> Result<(Computer, T), SnapshotError> {
+	let separator = format!("\n{}\n", SEPARATOR);
+	let mut sections = snapshot.splitn(2, separator.as_str());
+	let computer = Computer::restore(sections.next().unwrap_or(""))?;
+	let device = sections
+		.next()
+		.ok_or_else(|| SnapshotError("Missing device state".to_string()))?;
+
+	Ok((computer, T::restore(device)?))
+}
+
+#[test]
+fn test_round_trip() {
+	let mut computer = Computer::parse("3,0,4,0,99");
+	let mut io = Buffer::new(vec![1, 2]);
+
+	computer.set(1000, 7);
+	computer.step(&mut io).unwrap();
+
+	let snapshot = save(&computer, &io);
+	let expected = "pos 2
+base 0
+mem 0 1,0,4,0,99
+mem 1000 7
+---
+inputs 2
+outputs
+";
+
+	assert_eq!(snapshot, expected);
+
+	let (mut restored, mut io) = restore::<Buffer>(&snapshot).unwrap();
+
+	assert_eq!(restored.cells(), computer.cells());
+
+	restored.run(&mut io).unwrap();
+
+	assert_eq!(io.outputs, vec![1]);
+}
+
+#[test]
+fn test_errors() {
+	assert!(Computer::<Dense>::restore("pos x").is_err());
+	assert!(Computer::<Dense>::restore("mem 0 1,a").is_err());
+	assert!(Computer::<Dense>::restore("size 10").is_err());
+	assert!(restore::<Buffer>("pos 0\nbase 0\n").is_err());
+
+	let overflow = format!("mem {} 1,2", usize::MAX);
+
+	assert!(Computer::<Dense>::restore(&overflow).is_err());
+}
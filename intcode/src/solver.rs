@@ -0,0 +1,209 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::computer::Computer;
+use crate::io::Buffer;
+use crate::memory::{Dense, Memory};
+
+// How many steps an assignment gets unless `limit` says otherwise
+const LIMIT: usize = 1_000_000;
+
+type Prune<'a> = Box<dyn Fn(&[i64]) -> bool + Sync + 'a>;
+
+// Searches for the values to patch into a program so that it halts in a state
+// the caller is looking for, like the noun and verb of day 2.
+pub struct Solver<'a, M: Memory = Dense> {
+	computer: &'a Computer<M>,
+	patches: Vec<(usize, Range<i64>)>,
+	prune: Option<Prune<'a>>,
+	limit: usize,
+}
+
+impl<'a, M: Memory + Sync> Solver<'a, M> {
+	pub fn new(computer: &'a Computer<M>) -> Self {
+		Solver {
+			computer,
+			patches: Vec::new(),
+			prune: None,
+			limit: LIMIT,
+		}
+	}
+
+	// Tries every value in `values` at `addr`, in the order patches are added
+	pub fn patch(&mut self, addr: usize, values: Range<i64>) -> &mut Self {
+		self.patches.push((addr, values));
+		self
+	}
+
+	// Called with the values chosen for the first few patches. Returning
+	// false skips every assignment that starts with them.
+	pub fn prune<F>(&mut self, f: F) -> &mut Self
+	where
+		F: Fn(&[i64]) -> bool + Sync + 'a,
+	{
+		self.prune = Some(Box::new(f));
+		self
+	}
+
+	// Gives up on an assignment after this many steps, as a patched program
+	// may never halt. Defaults to a million.
+	pub fn limit(&mut self, steps: usize) -> &mut Self {
+		self.limit = steps;
+		self
+	}
+
+	// Every assignment, in order, for which the patched program halts and
+	// `predicate` accepts the halted machine and its outputs. Assignments that
+	// make the program fail, ask for input or run past the limit are skipped.
+	// The values of the first patch are shared out between a thread per core.
+	pub fn solve<P>(&self, predicate: P) -> Vec<Vec<i64>>
+	where
+		P: Fn(&Computer<M>, &[i64]) -> bool + Sync,
+	{
+		let first = match self.patches.first() {
+			Some((_, values)) => values.clone(),
+			None => {
+				return match self.check(&[], &predicate) {
+					true => vec![Vec::new()],
+					false => Vec::new(),
+				}
+			}
+		};
+
+		let len = first.end.saturating_sub(first.start).max(0) as usize;
+		let threads = thread::available_parallelism()
+			.map(|n| n.get())
+			.unwrap_or(1)
+			.min(len.max(1));
+		let next = AtomicUsize::new(0);
+		let found = Mutex::new(Vec::new());
+
+		thread::scope(|scope| {
+			for _ in 0..threads {
+				scope.spawn(|| loop {
+					let i = next.fetch_add(1, Ordering::Relaxed);
+
+					if i >= len {
+						break;
+					}
+
+					let mut values = vec![first.start + i as i64];
+					let mut solutions = Vec::new();
+
+					self.search(&mut values, &predicate, &mut solutions);
+					found.lock().unwrap().extend(solutions);
+				});
+			}
+		});
+
+		let mut found = found.into_inner().unwrap();
+
+		found.sort_unstable();
+		found
+	}
+
+	fn search<P>(
+		&self,
+		values: &mut Vec<i64>,
+		predicate: &P,
+		found: &mut Vec<Vec<i64>>,
+	) where
+		P: Fn(&Computer<M>, &[i64]) -> bool,
+	{
+		if let Some(prune) = self.prune.as_ref() {
+			if !prune(values) {
+				return;
+			}
+		}
+
+		match self.patches.get(values.len()) {
+			Some((_, range)) => {
+				for n in range.clone() {
+					values.push(n);
+					self.search(values, predicate, found);
+					values.pop();
+				}
+			}
+			None => {
+				if self.check(values, predicate) {
+					found.push(values.clone());
+				}
+			}
+		}
+	}
+
+	fn check<P>(&self, values: &[i64], predicate: &P) -> bool
+	where
+		P: Fn(&Computer<M>, &[i64]) -> bool,
+	{
+		let mut computer = self.computer.clone();
+		let mut io = Buffer::default();
+
+		for ((addr, _), n) in self.patches.iter().zip(values) {
+			computer.set(*addr, *n);
+		}
+
+		for _ in 0..self.limit {
+			match computer.step(&mut io) {
+				Ok(Some(_)) => return predicate(&computer, &io.outputs),
+				Ok(None) => {}
+				Err(_) => return false,
+			}
+		}
+
+		false
+	}
+}
+
+#[test]
+fn test_solve() {
+	// [0] = [1] * [2] and outputs [1] + [2]
+	let computer = Computer::parse("1102,0,0,0,1,1,2,11,4,11,99,0");
+	let mut solver = Solver::new(&computer);
+
+	solver.patch(1, 0..10).patch(2, 0..10);
+
+	assert_eq!(
+		solver.solve(|computer, _| computer.get(0) == 12),
+		vec![vec![2, 6], vec![3, 4], vec![4, 3], vec![6, 2]]
+	);
+
+	// Products of 35 whose sum, the output, is 12
+	assert_eq!(
+		solver.solve(|computer, outputs| {
+			computer.get(0) == 35 && outputs == [12]
+		}),
+		vec![vec![5, 7], vec![7, 5]]
+	);
+
+	solver.prune(|values| values.first().is_none_or(|n| n % 2 == 0));
+
+	assert_eq!(
+		solver.solve(|computer, _| computer.get(0) == 12),
+		vec![vec![2, 6], vec![4, 3], vec![6, 2]]
+	);
+}
+
+#[test]
+fn test_limit() {
+	// Loops forever unless [5] is zero
+	let computer = Computer::parse("1005,5,0,99,0,0");
+	let mut solver = Solver::new(&computer);
+
+	solver.patch(5, 0..3).limit(100);
+
+	assert_eq!(solver.solve(|_, _| true), vec![vec![0]]);
+	assert_eq!(
+		Solver::new(&computer).solve(|_, _| true),
+		vec![Vec::<i64>::new()]
+	);
+
+	// Without a limit of its own it gives up after the default
+	let mut solver = Solver::new(&computer);
+
+	solver.patch(5, 0..3);
+
+	assert_eq!(solver.solve(|_, _| true), vec![vec![0]]);
+}
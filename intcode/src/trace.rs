@@ -0,0 +1,302 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::computer::{Computer, Halt};
+use crate::disasm::Line;
+use crate::error::VmError;
+use crate::instruction::{Instruction, Opcode};
+use crate::io::{Buffer, Io};
+use crate::memory::Memory;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+	Input(i64),
+	Output(i64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+	pub base: i64,
+	pub line: Line,
+	pub reads: Vec<i64>,
+	pub write: Option<(usize, i64)>,
+	pub event: Option<Event>,
+}
+
+impl fmt::Display for Entry {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{:>6}: {} | base {}",
+			self.line.addr(),
+			self.line,
+			self.base
+		)?;
+
+		if !self.reads.is_empty() {
+			let reads =
+				self.reads.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+			write!(f, " | read {}", reads.join(", "))?;
+		}
+
+		if let Some((addr, value)) = self.write {
+			write!(f, " | write [{}] = {}", addr, value)?;
+		}
+
+		match self.event {
+			Some(Event::Input(n)) => write!(f, " | in {}", n),
+			Some(Event::Output(n)) => write!(f, " | out {}", n),
+			None => Ok(()),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+	pub line: usize,
+	pub expected: Option<String>,
+	pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let expected = self.expected.as_deref().unwrap_or("<end of trace>");
+
+		writeln!(f, "Trace diverged at line {}", self.line)?;
+		writeln!(f, "expected: {}", expected)?;
+		write!(f, "  actual: {}", self.actual)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceError {
+	Read(String),
+	Input { line: usize, value: String },
+	Mismatch(Mismatch),
+}
+
+impl fmt::Display for TraceError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TraceError::Read(err) => write!(f, "Failed to read trace: {}", err),
+			TraceError::Input { line, value } => {
+				write!(f, "Invalid input {} on line {}", value, line)
+			}
+			TraceError::Mismatch(mismatch) => write!(f, "{}", mismatch),
+		}
+	}
+}
+
+impl std::error::Error for TraceError {}
+
+impl<M: Memory> Computer<M> {
+	pub fn step_traced<T: Io>(
+		&mut self,
+		io: &mut T,
+	) -> Result<(Entry, Option<Halt>), VmError> {
+		let instruction = Instruction::decode(self.get(self.pos), self.pos)?;
+		let line = Line::decode(|i| self.get(i), self.pos);
+		let base = self.base;
+		let target = instruction.opcode.target();
+
+		let reads = (0..instruction.opcode.arity())
+			.filter(|i| Some(*i) != target)
+			.map(|i| self.index(instruction, i).map(|j| self.get(j)))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let addr = target.map(|i| self.index(instruction, i)).transpose()?;
+		let halt = self.step(io)?;
+		let write = match halt {
+			Some(_) => None,
+			None => addr.map(|addr| (addr, self.get(addr))),
+		};
+
+		let event = match (instruction.opcode, write) {
+			(Opcode::Input, Some((_, n))) => Some(Event::Input(n)),
+			(Opcode::Output, _) => Some(Event::Output(reads[0])),
+			_ => None,
+		};
+
+		let entry = Entry {
+			base,
+			line,
+			reads,
+			write,
+			event,
+		};
+
+		Ok((entry, halt))
+	}
+}
+
+fn describe(step: &Result<(Entry, Option<Halt>), VmError>) -> String {
+	match step {
+		Ok((entry, _)) => entry.to_string(),
+		Err(err) => format!("error: {}", err),
+	}
+}
+
+// A run that stops on an error, including running out of input, ends the
+// trace with an `error:` line so that replay can check it stops the same way.
+pub fn record<M, T, W>(
+	computer: &mut Computer<M>,
+	io: &mut T,
+	mut output: W,
+) -> std::io::Result<Result<Halt, VmError>>
+where
+	M: Memory,
+	T: Io,
+	W: Write,
+{
+	loop {
+		let step = computer.step_traced(io);
+
+		writeln!(output, "{}", describe(&step))?;
+
+		match step {
+			Ok((_, Some(halt))) => return Ok(Ok(halt)),
+			Ok((_, None)) => {}
+			Err(err) => return Ok(Err(err)),
+		}
+	}
+}
+
+pub fn inputs(trace: &str) -> Result<Vec<i64>, TraceError> {
+	trace
+		.lines()
+		.enumerate()
+		.filter_map(|(i, line)| Some((i, line.rsplit(" | ").next()?)))
+		.filter_map(|(i, event)| Some((i, event.strip_prefix("in ")?)))
+		.map(|(i, n)| {
+			n.parse::<i64>().map_err(|_| TraceError::Input {
+				line: i + 1,
+				value: n.to_string(),
+			})
+		})
+		.collect()
+}
+
+pub fn replay<M: Memory, R: BufRead>(
+	computer: &mut Computer<M>,
+	trace: R,
+) -> Result<Result<Halt, VmError>, TraceError> {
+	let lines = trace
+		.lines()
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|err| TraceError::Read(err.to_string()))?;
+
+	let mut io = Buffer::new(inputs(&lines.join("\n"))?);
+	let mut expected = lines.into_iter();
+
+	for line in 1.. {
+		let step = computer.step_traced(&mut io);
+		let actual = describe(&step);
+		let expected = expected.next();
+
+		if expected.as_ref() != Some(&actual) {
+			return Err(TraceError::Mismatch(Mismatch {
+				line,
+				expected,
+				actual,
+			}));
+		}
+
+		match step {
+			Ok((_, Some(halt))) => return Ok(Ok(halt)),
+			Ok((_, None)) => {}
+			Err(err) => return Ok(Err(err)),
+		}
+	}
+
+	unreachable!()
+}
+
+#[test]
+fn test_record() {
+	let mut computer = Computer::parse("3,9,1001,9,5,9,4,9,99,0");
+	let mut output = Vec::new();
+	let halt = record(&mut computer, &mut Buffer::new(vec![7]), &mut output);
+
+	let expected = "     0: IN -> [9] | base 0 | write [9] = 7 | in 7
+     2: ADD [9], #5 -> [9] | base 0 | read 7, 5 | write [9] = 12
+     6: OUT [9] | base 0 | read 12 | out 12
+     8: HLT | base 0
+";
+
+	assert_eq!(halt.unwrap(), Ok(Halt::Exit));
+	assert_eq!(String::from_utf8(output).unwrap(), expected);
+}
+
+#[test]
+fn test_record_starved() {
+	let mut computer = Computer::parse("3,0,3,0,99");
+	let mut output = Vec::new();
+	let halt = record(&mut computer, &mut Buffer::new(vec![7]), &mut output);
+
+	let expected = "     0: IN -> [0] | base 0 | write [0] = 7 | in 7
+error: Input required at 2
+";
+
+	assert_eq!(halt.unwrap(), Err(VmError::InputStarved { addr: 2 }));
+	assert_eq!(String::from_utf8(output).unwrap(), expected);
+	assert_eq!(
+		replay(&mut Computer::parse("3,0,3,0,99"), expected.as_bytes()),
+		Ok(Err(VmError::InputStarved { addr: 2 }))
+	);
+}
+
+#[test]
+fn test_replay() {
+	let computer = Computer::parse("3,11,3,12,1,11,12,13,4,13,99,0,0,0");
+	let mut trace = Vec::new();
+
+	record(
+		&mut computer.clone(),
+		&mut Buffer::new(vec![3, 4]),
+		&mut trace,
+	)
+	.unwrap()
+	.unwrap();
+
+	assert_eq!(
+		inputs(&String::from_utf8(trace.clone()).unwrap()),
+		Ok(vec![3, 4])
+	);
+	assert_eq!(
+		replay(&mut computer.clone(), &trace[..]),
+		Ok(Ok(Halt::Exit))
+	);
+
+	let mut patched = computer.clone();
+
+	patched.set(4, 2);
+
+	let mismatch = match replay(&mut patched, &trace[..]) {
+		Err(TraceError::Mismatch(mismatch)) => mismatch,
+		other => panic!("Expected a mismatch, found {:?}", other),
+	};
+
+	assert_eq!(mismatch.line, 3);
+	assert_eq!(
+		mismatch.actual,
+		"     4: MUL [11], [12] -> [13] | base 0 | read 3, 4 | write [13] = 12"
+	);
+}
+
+#[test]
+fn test_replay_malformed() {
+	let trace = "     0: IN -> [0] | base 0 | write [0] = 7 | in seven\n";
+
+	assert_eq!(
+		replay(&mut Computer::parse("3,0,99"), trace.as_bytes()),
+		Err(TraceError::Input {
+			line: 1,
+			value: "seven".to_string()
+		})
+	);
+	assert!(matches!(
+		replay(&mut Computer::parse("3,0,99"), &[0xff, b'\n'][..]),
+		Err(TraceError::Read(_))
+	));
+}
@@ -0,0 +1,331 @@
+use std::fmt::Write;
+
+use crate::disasm::{reachable, Line};
+use crate::instruction::{Mode, Opcode};
+
+const HEADER: &str = "\
+// Generated from an Intcode program by `intcode::transpile`
+
+use intcode::{Computer, Halt, Io, Memory, VmError};
+
+const NONE: usize = usize::MAX;
+
+fn address(target: i64, addr: usize) -> Result<usize, VmError> {
+	match target < 0 {
+		true => Err(VmError::NegativeAddress { addr, target }),
+		false => Ok(target as usize),
+	}
+}
+
+fn relative(base: i64, k: i64, addr: usize) -> Result<usize, VmError> {
+	address(base.checked_add(k).ok_or(VmError::Overflow { addr })?, addr)
+}
+
+fn mark(patched: &mut [bool], j: usize) {
+	if let Some(&owner) = OWNER.get(j) {
+		if owner != NONE {
+			patched[owner] = true;
+		}
+	}
+}
+
+// Runs compiled code for every instruction that still holds what it did when
+// the program was compiled, and interprets the rest
+#[allow(unused_variables, unreachable_code, clippy::all)]
+pub fn run<M: Memory, T: Io>(
+	computer: &mut Computer<M>,
+	io: &mut T,
+) -> Result<Halt, VmError> {
+	let mut patched = vec![false; PROGRAM.len()];
+
+	for (i, owner) in OWNER.iter().enumerate() {
+		if *owner != NONE && computer.get(i) != PROGRAM[i] {
+			patched[*owner] = true;
+		}
+	}
+
+	loop {
+		match computer.pos {
+";
+
+const FOOTER: &str = "\
+			_ => {
+				if let Some(j) = computer.write_target() {
+					mark(&mut patched, j);
+				}
+
+				if let Some(halt) = computer.step(io)? {
+					return Ok(halt);
+				}
+			}
+		}
+	}
+}
+";
+
+// An expression for the value of a parameter
+fn operand(mode: Mode, value: i64, addr: usize) -> String {
+	match mode {
+		Mode::Immediate => value.to_string(),
+		Mode::Position if value >= 0 => format!("computer.get({})", value),
+		Mode::Position => {
+			format!("computer.get(address({}, {})?)", value, addr)
+		}
+		Mode::Relative => format!(
+			"computer.get(relative(computer.base, {}, {})?)",
+			value, addr
+		),
+	}
+}
+
+// An expression for the cell a parameter writes to, and the cell itself if
+// it is known without running anything
+fn target(mode: Mode, value: i64, addr: usize) -> (String, Option<usize>) {
+	match mode {
+		Mode::Position if value >= 0 => {
+			(value.to_string(), Some(value as usize))
+		}
+		Mode::Relative => (
+			format!("relative(computer.base, {}, {})?", value, addr),
+			None,
+		),
+		_ => (format!("address({}, {})?", value, addr), None),
+	}
+}
+
+fn fold(opcode: Opcode, x: i64, y: i64) -> Option<i64> {
+	match opcode {
+		Opcode::Add => x.checked_add(y),
+		Opcode::Multiply => x.checked_mul(y),
+		Opcode::LessThan => Some((x < y) as i64),
+		Opcode::Equals => Some((x == y) as i64),
+		_ => None,
+	}
+}
+
+// Compiles a program into a Rust module with a `run` function that behaves
+// like `Computer::run`. Each instruction reachable from the start becomes a
+// match arm with its immediate operands, and any arithmetic on them, folded
+// in. An instruction whose cells have been written since, or that was never
+// compiled, is interpreted instead. The module refers to the `intcode`
+// crate, so it can be written out by a build script and `include!`d.
+pub fn transpile(nums: &[i64]) -> String {
+	let mut owner = vec![None; nums.len()];
+	let mut code = Vec::new();
+
+	for (addr, instruction) in reachable(nums) {
+		let cells = addr..(addr + instruction.size());
+		let writes_immediate = instruction
+			.opcode
+			.target()
+			.is_some_and(|i| instruction.modes[i] == Mode::Immediate);
+
+		// Instructions that overlap one already compiled, or that always fail,
+		// are left to the interpreter
+		if writes_immediate || owner[cells.clone()].iter().any(Option::is_some)
+		{
+			continue;
+		}
+
+		for i in cells {
+			owner[i] = Some(addr);
+		}
+
+		code.push((addr, instruction));
+	}
+
+	let list = |values: Vec<String>| {
+		values
+			.chunks(12)
+			.map(|chunk| format!("\t{},\n", chunk.join(", ")))
+			.collect::<String>()
+	};
+
+	let mut out = HEADER.to_string();
+
+	for (addr, instruction) in code {
+		let line = Line::decode(|i| nums[i], addr);
+		let param = |i: usize| (instruction.modes[i], nums[addr + i + 1]);
+		let next = addr + instruction.size();
+		let arg = |i: usize| {
+			let (mode, value) = param(i);
+
+			operand(mode, value, addr)
+		};
+		let mark = |cell: Option<usize>| match cell {
+			Some(j) => match owner.get(j).cloned().flatten() {
+				Some(owner) => format!("patched[{}] = true;\n", owner),
+				None => String::new(),
+			},
+			None => "mark(&mut patched, j);\n".to_string(),
+		};
+
+		let mut body = String::new();
+
+		match instruction.opcode {
+			Opcode::Add
+			| Opcode::Multiply
+			| Opcode::LessThan
+			| Opcode::Equals => {
+				let (mode, value) = param(2);
+				let (j, cell) = target(mode, value, addr);
+				let folded = match (param(0), param(1)) {
+					((Mode::Immediate, x), (Mode::Immediate, y)) => {
+						fold(instruction.opcode, x, y)
+					}
+					_ => None,
+				};
+
+				let value = match folded {
+					Some(n) => n.to_string(),
+					None => {
+						let _ = writeln!(body, "let x = {};", arg(0));
+						let _ = writeln!(body, "let y = {};", arg(1));
+
+						let overflow = format!(
+							".ok_or(VmError::Overflow {{ addr: {} }})?",
+							addr
+						);
+
+						match instruction.opcode {
+							Opcode::Add => {
+								format!("i64::checked_add(x, y){}", overflow)
+							}
+							Opcode::Multiply => {
+								format!("i64::checked_mul(x, y){}", overflow)
+							}
+							Opcode::LessThan => "(x < y) as i64".to_string(),
+							_ => "(x == y) as i64".to_string(),
+						}
+					}
+				};
+
+				let _ = writeln!(body, "let j = {};", j);
+				let _ = writeln!(body, "computer.set(j, {});", value);
+				body += &mark(cell);
+				let _ = writeln!(body, "computer.pos = {};", next);
+			}
+			Opcode::Input => {
+				let (mode, value) = param(0);
+				let (j, cell) = target(mode, value, addr);
+
+				let _ = writeln!(body, "let j = {};", j);
+				let _ = writeln!(
+					body,
+					"let input = io.input().ok_or(VmError::InputStarved {{ addr: {} }})?;",
+					addr
+				);
+				let _ = writeln!(body, "computer.set(j, input);");
+				body += &mark(cell);
+				let _ = writeln!(body, "computer.pos = {};", next);
+			}
+			Opcode::Output => {
+				let _ = writeln!(body, "io.output({});", arg(0));
+				let _ = writeln!(body, "computer.pos = {};", next);
+			}
+			Opcode::IncrementBase => {
+				let _ = writeln!(
+					body,
+					"computer.base = computer.base.checked_add({}).ok_or(VmError::Overflow {{ addr: {} }})?;",
+					arg(0),
+					addr
+				);
+				let _ = writeln!(body, "computer.pos = {};", next);
+			}
+			Opcode::JumpTrue | Opcode::JumpFalse => {
+				let when = instruction.opcode == Opcode::JumpTrue;
+
+				if param(0).0 != Mode::Immediate {
+					let _ = writeln!(body, "let x = {};", arg(0));
+				}
+
+				let jump = match param(1) {
+					(Mode::Immediate, n) if n >= 0 => n.to_string(),
+					(Mode::Immediate, n) => {
+						format!("address({}, {})?", n, addr)
+					}
+					_ => {
+						let _ = writeln!(body, "let y = {};", arg(1));
+
+						format!("address(y, {})?", addr)
+					}
+				};
+
+				match param(0) {
+					(Mode::Immediate, n) => {
+						let pos = match (n != 0) == when {
+							true => jump,
+							false => next.to_string(),
+						};
+
+						let _ = writeln!(body, "computer.pos = {};", pos);
+					}
+					_ => {
+						let _ = writeln!(
+							body,
+							"computer.pos = match x {} 0 {{\n\ttrue => {},\n\tfalse => {},\n}};",
+							if when { "!=" } else { "==" },
+							jump,
+							next
+						);
+					}
+				}
+			}
+			Opcode::Halt => {
+				let _ = writeln!(body, "return Ok(Halt::Exit);");
+			}
+		}
+
+		let _ = writeln!(out, "\t\t\t// {}", line);
+		let _ = writeln!(out, "\t\t\t{} if !patched[{}] => {{", addr, addr);
+
+		for line in body.lines() {
+			let _ = writeln!(out, "\t\t\t\t{}", line);
+		}
+
+		let _ = writeln!(out, "\t\t\t}}");
+	}
+
+	out += FOOTER;
+
+	let _ = write!(
+		out,
+		"\nconst PROGRAM: [i64; {}] = [\n{}];\n",
+		nums.len(),
+		list(nums.iter().map(|n| n.to_string()).collect())
+	);
+	let _ = write!(
+		out,
+		"\n// The compiled instruction each cell belongs to\nconst OWNER: [usize; {}] = [\n{}];\n",
+		nums.len(),
+		list(
+			owner
+				.iter()
+				.map(|owner| match owner {
+					Some(addr) => addr.to_string(),
+					None => "NONE".to_string(),
+				})
+				.collect()
+		)
+	);
+
+	out
+}
+
+#[test]
+fn test_transpile() {
+	// Outputs 2 + 3, then halts
+	let out = transpile(&[1101, 2, 3, 7, 4, 7, 99, 0]);
+
+	assert!(out.contains("\t\t\t0 if !patched[0] => {\n"));
+	assert!(out.contains("computer.set(j, 5);\n"));
+	assert!(out.contains("io.output(computer.get(7));\n"));
+	assert!(out.contains(
+		"const OWNER: [usize; 8] = [\n\t0, 0, 0, 0, 4, 4, 6, NONE,\n];"
+	));
+
+	// Writing over the operand of the output interprets it from then on
+	let out = transpile(&[1101, 2, 3, 5, 4, 7, 99, 0]);
+
+	assert!(out.contains("patched[4] = true;\n"));
+}
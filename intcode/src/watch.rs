@@ -0,0 +1,193 @@
+use crate::computer::Computer;
+use crate::error::VmError;
+use crate::instruction::Instruction;
+use crate::io::Io;
+use crate::memory::{Dense, Memory};
+
+#[cfg(test)]
+use crate::io::Buffer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+	Read,
+	Write,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+	Continue,
+	Pause,
+}
+
+// An access to a watched cell by the instruction at `pos`. A read leaves
+// `old` and `new` the same.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+	pub pos: usize,
+	pub addr: usize,
+	pub access: Access,
+	pub old: i64,
+	pub new: i64,
+}
+
+type Callback<'a, M> = Box<dyn FnMut(&mut Computer<M>, &Hit) -> Action + 'a>;
+
+struct Hook<'a, M: Memory> {
+	addr: usize,
+	access: Access,
+	callback: Callback<'a, M>,
+}
+
+// Runs a machine, calling hooks after any instruction that reads or writes
+// the cells they watch. Hooks may change the machine, to patch a program as
+// it runs, and may pause it.
+pub struct Watcher<'a, M: Memory = Dense> {
+	pub computer: Computer<M>,
+	hooks: Vec<Hook<'a, M>>,
+}
+
+impl<'a, M: Memory> Watcher<'a, M> {
+	pub fn new(computer: Computer<M>) -> Self {
+		Watcher {
+			computer,
+			hooks: Vec::new(),
+		}
+	}
+
+	pub fn hook<F>(&mut self, addr: usize, access: Access, f: F) -> &mut Self
+	where
+		F: FnMut(&mut Computer<M>, &Hit) -> Action + 'a,
+	{
+		self.hooks.push(Hook {
+			addr,
+			access,
+			callback: Box::new(f),
+		});
+
+		self
+	}
+
+	// A hook that does nothing but pause
+	pub fn watch(&mut self, addr: usize, access: Access) -> &mut Self {
+		self.hook(addr, access, |_, _| Action::Pause)
+	}
+
+	// Every hook for an instruction is called, in the order they were added,
+	// before stopping on the first hit that asked to pause. Returns `None`
+	// once the program halts.
+	pub fn run<T: Io>(&mut self, io: &mut T) -> Result<Option<Hit>, VmError> {
+		loop {
+			let pos = self.computer.pos;
+			let instruction = Instruction::decode(self.computer.get(pos), pos)?;
+			let target = instruction.opcode.target();
+
+			let mut hits = self
+				.computer
+				.operands(instruction)
+				.into_iter()
+				.map(|(i, addr)| Hit {
+					pos,
+					addr,
+					access: match Some(i) == target {
+						true => Access::Write,
+						false => Access::Read,
+					},
+					old: self.computer.get(addr),
+					new: 0,
+				})
+				.filter(|hit| {
+					self.hooks.iter().any(|hook| {
+						hook.addr == hit.addr && hook.access == hit.access
+					})
+				})
+				.collect::<Vec<_>>();
+
+			let halt = self.computer.step(io)?;
+			let mut pause = None;
+
+			for hit in hits.iter_mut() {
+				hit.new = match hit.access {
+					Access::Read => hit.old,
+					Access::Write => self.computer.get(hit.addr),
+				};
+
+				for hook in self.hooks.iter_mut() {
+					if hook.addr != hit.addr || hook.access != hit.access {
+						continue;
+					}
+
+					let action = (hook.callback)(&mut self.computer, hit);
+
+					if action == Action::Pause && pause.is_none() {
+						pause = Some(*hit);
+					}
+				}
+			}
+
+			if pause.is_some() {
+				return Ok(pause);
+			}
+
+			if halt.is_some() {
+				return Ok(None);
+			}
+		}
+	}
+}
+
+#[test]
+fn test_watch() {
+	// Counts [9] down from 3
+	let computer = Computer::parse("1001,9,-1,9,1005,9,0,99,0,3");
+	let mut watcher = Watcher::new(computer);
+
+	watcher.watch(9, Access::Write);
+
+	let hit = watcher.run(&mut Buffer::default()).unwrap();
+
+	assert_eq!(
+		hit,
+		Some(Hit {
+			pos: 0,
+			addr: 9,
+			access: Access::Write,
+			old: 3,
+			new: 2,
+		})
+	);
+	assert_eq!(watcher.computer.pos, 4);
+
+	let mut watcher = Watcher::new(watcher.computer);
+
+	watcher.watch(9, Access::Read);
+
+	// Stops on the read by the jump before the next write
+	assert_eq!(watcher.run(&mut Buffer::default()).unwrap().unwrap().pos, 4);
+}
+
+#[test]
+fn test_hook() {
+	// Counts [9] down from 3, but a hook keeps it from going below 1 the first
+	// time it gets there
+	let computer = Computer::parse("1001,9,-1,9,1005,9,0,99,0,3");
+	let mut writes = Vec::new();
+	let mut patched = false;
+	let mut watcher = Watcher::new(computer);
+
+	watcher.hook(9, Access::Write, |computer, hit| {
+		writes.push(hit.new);
+
+		if hit.new == 0 && !patched {
+			computer.set(9, 2);
+			patched = true;
+		}
+
+		Action::Continue
+	});
+
+	assert_eq!(watcher.run(&mut Buffer::default()), Ok(None));
+
+	drop(watcher);
+
+	assert_eq!(writes, vec![2, 1, 0, 1, 0]);
+}